@@ -0,0 +1,91 @@
+// the tree returned by `parse`. rendering the blocks one after another reproduces the whole message, so there is
+// no separator between blocks - line breaks are kept as `InlineKind::LineBreak` inside paragraphs.
+use std::ops::Range;
+
+/// a parsed message
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Document {
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub kind: BlockKind,
+    /// byte range of the markdown this block came from, including the markers
+    pub span: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    /// plain text and inline markdown. there is no wrapping `<p>` tag
+    Paragraph(Vec<Inline>),
+    /// `# heading` through `##### heading`
+    Heading { level: u8, content: Vec<Inline> },
+    /// successive `> ` lines. each line is a separate entry.
+    BlockQuote(Vec<Vec<Inline>>),
+    /// text between triple backticks. if there is more than one word, the first word (or line) is the language.
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+    },
+    /// `- x`, `* x` and `+ x` make a bulleted list. `1. x` or `1) x` make a numbered list which starts at `start`.
+    List {
+        start: Option<u64>,
+        items: Vec<ListItem>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListItem {
+    pub content: Vec<Inline>,
+    /// lists nested under this item
+    pub children: Vec<Block>,
+    /// from the marker to the end of the item, including nested lists
+    pub span: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inline {
+    pub kind: InlineKind,
+    /// byte range of the markdown this element came from, including the markers
+    pub span: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InlineKind {
+    Text(String),
+    LineBreak,
+    /// `*x*` or `_x_`
+    Emphasis(Vec<Inline>),
+    /// `**x**` or `__x__`
+    Strong(Vec<Inline>),
+    /// `__x__` when `Options::underscore_underline` is set
+    Underline(Vec<Inline>),
+    /// `~~x~~`
+    Strikethrough(Vec<Inline>),
+    /// `||x||`
+    Spoiler(Vec<Inline>),
+    /// `` `x` ``
+    Code(String),
+    /// `[x](url)` or a bare url. `url` is what goes in the href, and was checked against `Options::link_schemes`.
+    Link {
+        url: String,
+        content: Vec<Inline>,
+    },
+    /// `@name` or `@did:method:id`, without the `@`
+    Mention(String),
+    /// `:name:`, or an emoticon like `:)`. the name doesn't include the colons, and may not be a known emoji.
+    Emoji(String),
+}
+
+impl Block {
+    pub fn new(kind: BlockKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Inline {
+    pub fn new(kind: InlineKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
@@ -0,0 +1,39 @@
+// problems with the markdown which made it show up as text, for editors to underline
+use std::{fmt, ops::Range};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// byte range of the markdown with the problem
+    pub span: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// a marker like `**` or ```` ``` ```` which is never closed. the span is the marker.
+    Unclosed,
+    /// a pair of markers with nothing but whitespace between them, like `~~ ~~`. the span covers both markers.
+    Empty,
+    /// `#heading`. the span is the line.
+    HeadingMissingSpace,
+    /// `>quote`. the span is the line.
+    BlockQuoteMissingSpace,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            DiagnosticKind::Unclosed => "this marker is never closed",
+            DiagnosticKind::Empty => "there is nothing between these markers",
+            DiagnosticKind::HeadingMissingSpace => "a heading needs a space after the #",
+            DiagnosticKind::BlockQuoteMissingSpace => "a block quote needs a space after the >",
+        };
+        f.write_str(message)
+    }
+}
@@ -0,0 +1,79 @@
+// `:shortcode:` and emoticons like `:)`. the names are the ones github uses.
+use std::collections::HashMap;
+
+/// custom emoji, by name. pass it to `HtmlRenderer::with_custom_emoji` to render `:name:` as an image.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EmojiRegistry {
+    emojis: HashMap<String, String>,
+}
+
+impl EmojiRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `name` doesn't include the colons. `src` is the url of the image.
+    pub fn insert(&mut self, name: impl Into<String>, src: impl Into<String>) {
+        self.emojis.insert(name.into(), src.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.emojis.get(name).map(String::as_str)
+    }
+}
+
+// `text` starts after the first colon. returns the length of the name.
+pub(crate) fn shortcode_len(text: &str) -> Option<usize> {
+    let len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')))
+        .unwrap_or(text.len());
+    (len > 0 && text[len..].starts_with(':')).then_some(len)
+}
+
+// returns the shortcode and length of the emoticon at the start of the text
+pub(crate) fn emoticon(text: &str) -> Option<(&'static str, usize)> {
+    let (name, len) = [
+        (":)", "slightly_smiling_face"),
+        (":(", "slightly_frowning_face"),
+        ("<3", "heart"),
+    ]
+    .into_iter()
+    .find(|(emoticon, _)| text.starts_with(emoticon))
+    .map(|(emoticon, name)| (name, emoticon.len()))?;
+
+    // so things like `f(x:(y))` are left alone
+    let is_word = text[len..]
+        .chars()
+        .next()
+        .map(char::is_alphanumeric)
+        .unwrap_or_default();
+    (!is_word).then_some((name, len))
+}
+
+/// the unicode emoji for a shortcode, like `😄` for `smile`
+pub fn unicode_emoji(name: &str) -> Option<&'static str> {
+    emojis::get_by_shortcode(name).map(|x| x.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortcode_len() {
+        assert_eq!(shortcode_len("smile: hi"), Some(5));
+        assert_eq!(shortcode_len("+1:"), Some(2));
+        assert_eq!(shortcode_len(":"), None);
+        assert_eq!(shortcode_len("smile"), None);
+        assert_eq!(shortcode_len("a b:"), None);
+    }
+
+    #[test]
+    fn test_emoticon() {
+        assert_eq!(emoticon(":) hi"), Some(("slightly_smiling_face", 2)));
+        assert_eq!(emoticon("<3"), Some(("heart", 2)));
+        assert_eq!(emoticon(":(x"), None);
+        assert_eq!(unicode_emoji("heart"), Some("❤️"));
+        assert_eq!(unicode_emoji("not_an_emoji"), None);
+    }
+}
@@ -0,0 +1,219 @@
+// finds search terms in a Document, for highlighting with `<mark>`. the text of each line is searched as a whole,
+// so a match can start in one node and end in another, like `foo**bar**`. the matches are stored per node, and
+// `HtmlRenderer` looks them up with the span of the node it is rendering.
+use std::{collections::HashMap, ops::Range};
+
+use crate::ast::{Block, BlockKind, Document, Inline, InlineKind};
+
+/// the words to search for. matching ignores case.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    terms: Vec<String>,
+    code: bool,
+}
+
+/// where a `Query` matched a `Document`. pass it to `HtmlRenderer::with_highlights`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Highlights {
+    // the ranges to highlight in each text or code node, by the start of its span
+    nodes: HashMap<usize, Vec<Range<usize>>>,
+}
+
+impl Query {
+    /// each word is a separate term
+    pub fn new(query: &str) -> Self {
+        // lowercased one character at a time, the same way as the text. `str::to_lowercase` treats a final sigma
+        // differently.
+        let lowercase = |term: &str| term.chars().flat_map(char::to_lowercase).collect();
+        Self {
+            terms: query.split_whitespace().map(lowercase).collect(),
+            code: false,
+        }
+    }
+
+    /// also highlights matches inside of code and code blocks
+    pub fn in_code(mut self, code: bool) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn find(&self, document: &Document) -> Highlights {
+        let mut finder = Finder {
+            query: self,
+            line: String::new(),
+            nodes: Vec::new(),
+            highlights: Highlights::default(),
+        };
+        finder.blocks(&document.blocks);
+        finder.highlights
+    }
+}
+
+impl Highlights {
+    // the ranges of the node's text to highlight
+    pub(crate) fn get(&self, start: usize) -> Option<&[Range<usize>]> {
+        self.nodes.get(&start).map(Vec::as_slice)
+    }
+}
+
+struct Finder<'a> {
+    query: &'a Query,
+    // the text of the current line
+    line: String,
+    // the span start of each node in the line, and where its text is in `line`
+    nodes: Vec<(usize, Range<usize>)>,
+    highlights: Highlights,
+}
+
+impl Finder<'_> {
+    fn blocks(&mut self, blocks: &[Block]) {
+        for block in blocks {
+            match &block.kind {
+                BlockKind::Paragraph(content) | BlockKind::Heading { content, .. } => {
+                    self.inlines(content)
+                }
+                BlockKind::BlockQuote(lines) => {
+                    for line in lines {
+                        self.inlines(line);
+                        self.end_line();
+                    }
+                }
+                BlockKind::CodeBlock { code, .. } if self.query.code => {
+                    self.node(block.span.start, code)
+                }
+                BlockKind::CodeBlock { .. } => {}
+                BlockKind::List { items, .. } => {
+                    for item in items {
+                        self.inlines(&item.content);
+                        self.end_line();
+                        self.blocks(&item.children);
+                    }
+                }
+            }
+            self.end_line();
+        }
+    }
+
+    fn inlines(&mut self, inlines: &[Inline]) {
+        for inline in inlines {
+            match &inline.kind {
+                InlineKind::Text(text) => self.node(inline.span.start, text),
+                InlineKind::Code(code) if self.query.code => self.node(inline.span.start, code),
+                InlineKind::Emphasis(content)
+                | InlineKind::Strong(content)
+                | InlineKind::Underline(content)
+                | InlineKind::Strikethrough(content)
+                | InlineKind::Spoiler(content)
+                | InlineKind::Link { content, .. } => self.inlines(content),
+                // nothing matches across these
+                InlineKind::Code(_)
+                | InlineKind::LineBreak
+                | InlineKind::Mention(_)
+                | InlineKind::Emoji(_) => self.end_line(),
+            }
+        }
+    }
+
+    fn node(&mut self, start: usize, text: &str) {
+        let range = self.line.len()..self.line.len() + text.len();
+        self.line += text;
+        self.nodes.push((start, range));
+    }
+
+    // searches the line, and splits the matches up between its nodes. both are in order, so they are walked together.
+    fn end_line(&mut self) {
+        let matches = self.matches();
+        // the first match which doesn't end before the current node
+        let mut next = 0;
+        for (start, range) in self.nodes.drain(..) {
+            while matches.get(next).is_some_and(|x| x.end <= range.start) {
+                next += 1;
+            }
+            let ranges: Vec<Range<usize>> = matches[next..]
+                .iter()
+                .take_while(|x| x.start < range.end)
+                .map(|x| x.start.max(range.start) - range.start..x.end.min(range.end) - range.start)
+                .collect();
+            if !ranges.is_empty() {
+                self.highlights.nodes.insert(start, ranges);
+            }
+        }
+        self.line.clear();
+    }
+
+    // the ranges of `line` which match a term, sorted and without overlaps
+    fn matches(&self) -> Vec<Range<usize>> {
+        // lowercasing can change the length of a character, so each byte of the lowercase text remembers which
+        // character it came from
+        let mut lower = String::new();
+        let mut chars = Vec::new();
+        for (idx, original) in self.line.char_indices() {
+            for c in original.to_lowercase() {
+                lower.push(c);
+                chars.resize(lower.len(), idx..idx + original.len_utf8());
+            }
+        }
+
+        let mut matches: Vec<Range<usize>> = Vec::new();
+        for term in self.query.terms.iter().filter(|x| !x.is_empty()) {
+            for (start, _) in lower.match_indices(term.as_str()) {
+                let end = start + term.len();
+                matches.push(chars[start].start..chars[end - 1].end);
+            }
+        }
+        matches.sort_by_key(|x| x.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::new();
+        for range in matches {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_to_html_highlighted;
+
+    #[test]
+    fn test_highlight() {
+        let query = Query::new("FOO bar");
+        let test_str = "foo**bar** Foo `foo` <foo>\n> BARfoo";
+        let expected = "<mark>foo</mark><strong><mark>bar</mark></strong> <mark>Foo</mark> <code>foo</code> &lt;<mark>foo</mark>&gt;\n<blockquote>\n<p><mark>BARfoo</mark></p>\n</blockquote>";
+        assert_eq!(text_to_html_highlighted(test_str, &query).0, expected);
+
+        // a term can cross emphasis, but not a line break
+        let query = Query::new("foobar");
+        let test_str = "foo*bar* foo\nbar";
+        let expected = "<mark>foo</mark><em><mark>bar</mark></em> foo\nbar";
+        assert_eq!(text_to_html_highlighted(test_str, &query).0, expected);
+    }
+
+    #[test]
+    fn test_highlight_code() {
+        let query = Query::new("rust let").in_code(true);
+        let test_str = "```rust\nlet a = 0;``` `LET` [let](https://let.com)";
+        let expected = "<pre><code class=\"language-rust\"><mark>let</mark> a = 0;</code></pre> <code><mark>LET</mark></code> <a href=\"https://let.com\"><mark>let</mark></a>";
+        assert_eq!(text_to_html_highlighted(test_str, &query).0, expected);
+    }
+
+    #[test]
+    fn test_highlight_case() {
+        // lowercasing `İ` makes it longer
+        let query = Query::new("i̇x");
+        let (html, ranges) = text_to_html_highlighted("aİx", &query);
+        assert_eq!(html, "a<mark>İx</mark>");
+        assert_eq!(ranges, vec![0..html.len()]);
+
+        // `Σ` at the end of a word is still `σ`, not `ς`
+        let query = Query::new("ΟΔΟΣ");
+        assert_eq!(
+            text_to_html_highlighted("ΟΔΟΣ", &query).0,
+            "<mark>ΟΔΟΣ</mark>"
+        );
+    }
+}
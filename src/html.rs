@@ -0,0 +1,556 @@
+// the default renderer
+use std::{
+    fmt::{self, Write},
+    io,
+    ops::Range,
+};
+
+use crate::{
+    ast::Document,
+    emoji::{self, EmojiRegistry},
+    highlight::Highlights,
+    mentions::Mention,
+    render::Renderer,
+    segments::{self, Segment, SegmentKind},
+    transform::{HtmlOutput, TextTransformer},
+};
+
+impl Document {
+    /// renders the document the same way as `text_to_html`
+    pub fn to_html(&self) -> (String, Vec<Range<usize>>) {
+        let mut renderer = HtmlRenderer::new();
+        self.render(&mut renderer);
+        renderer.finish()
+    }
+
+    /// like `to_html`, but the html is written straight to `output` instead of being collected
+    pub fn write_html(&self, output: &mut impl fmt::Write) -> fmt::Result {
+        let mut renderer = HtmlRenderer::writing_to(output);
+        self.render(&mut renderer);
+        renderer.finish_writing().map(|_| ())
+    }
+
+    /// like `write_html`, for files and sockets. the html is written in small pieces, so `output` should be
+    /// buffered.
+    pub fn write_html_io(&self, output: &mut impl io::Write) -> io::Result<()> {
+        let mut writer = IoWriter {
+            output,
+            error: None,
+        };
+        self.write_html(&mut writer)
+            .map_err(|_| writer.error.unwrap_or_else(|| io::Error::other(fmt::Error)))
+    }
+
+    /// renders the document the same way as `text_to_html_segments`
+    pub fn to_html_segments(&self) -> (String, Vec<Segment>) {
+        let mut renderer = HtmlRenderer::new();
+        self.render(&mut renderer);
+        renderer.finish_segments()
+    }
+}
+
+// lets `write_html` write to an `io::Write`, and keeps the error it returned
+struct IoWriter<'a, W> {
+    output: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.output.write_all(s.as_bytes()).map_err(|x| {
+            self.error = Some(x);
+            fmt::Error
+        })
+    }
+}
+
+// where the html goes. it counts what has been written, for the segments, and stops at the first error.
+struct Output<W> {
+    html: W,
+    len: usize,
+    result: fmt::Result,
+}
+
+impl<W: fmt::Write> fmt::Write for Output<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.result.is_ok() {
+            self.result = self.html.write_str(s);
+            self.len += s.len();
+        }
+        self.result
+    }
+}
+
+type Resolver<'a> = Box<dyn Fn(&str) -> Mention + 'a>;
+
+/// renders a `Document` as html. this is what `text_to_html` uses. the html is collected into a `String`, or written
+/// to any `fmt::Write` with `HtmlRenderer::writing_to`.
+pub struct HtmlRenderer<'a, W = String> {
+    output: Output<W>,
+    // only collected into a string, since nothing reads them when writing somewhere else
+    segments: Option<Vec<Segment>>,
+    // the markdown being rendered, from `Renderer::source`
+    source: Range<usize>,
+    // block quote lines and list items are separated by newlines
+    first_line: bool,
+    // where the html and markdown of the current link start. everything inside a link is part of its segment.
+    link: Option<(usize, usize)>,
+    // the text of a bare url is the url, so it isn't transformed
+    autolink: bool,
+    resolver: Option<Resolver<'a>>,
+    // ids of the resolved mentions
+    mentions: Vec<String>,
+    custom_emoji: Option<&'a EmojiRegistry>,
+    // defaults to `spoiler`
+    spoiler_class: Option<String>,
+    transformer: Option<Box<dyn TextTransformer + 'a>>,
+    // text waiting to be passed to the transformer, and the markdown it came from
+    pending: String,
+    pending_source: Range<usize>,
+    highlights: Option<Highlights>,
+}
+
+impl Default for HtmlRenderer<'_> {
+    fn default() -> Self {
+        let mut renderer = Self::writing_to(String::new());
+        renderer.segments = Some(Vec::new());
+        renderer
+    }
+}
+
+impl HtmlRenderer<'_> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns the html and a list of indices which point to sections that aren't code
+    pub fn finish(self) -> (String, Vec<Range<usize>>) {
+        let (html, segments) = self.finish_segments();
+        let ranges = segments::text_ranges(&segments);
+        (html, ranges)
+    }
+
+    /// returns the html and what each part of it is
+    pub fn finish_segments(mut self) -> (String, Vec<Segment>) {
+        self.flush();
+        (self.output.html, self.segments.unwrap_or_default())
+    }
+}
+
+impl<'a, W: fmt::Write> HtmlRenderer<'a, W> {
+    /// writes the html to `output` as it is rendered, without keeping any of it. call `finish_writing` at the end.
+    pub fn writing_to(output: W) -> Self {
+        Self {
+            output: Output {
+                html: output,
+                len: 0,
+                result: Ok(()),
+            },
+            segments: None,
+            source: 0..0,
+            first_line: false,
+            link: None,
+            autolink: false,
+            resolver: None,
+            mentions: Vec::new(),
+            custom_emoji: None,
+            spoiler_class: None,
+            transformer: None,
+            pending: String::new(),
+            pending_source: 0..0,
+            highlights: None,
+        }
+    }
+
+    /// looks up each `@name` with `resolver`. users are rendered as
+    /// `<span class="mention" data-id="id" data-name="name">@display name</span>`, and unknown mentions are left
+    /// as text. without a resolver, every mention is text.
+    pub fn with_mentions(mut self, resolver: impl Fn(&str) -> Mention + 'a) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// renders `:name:` as `<img class="emoji" src="src" alt=":name:">` for the emoji in the registry. they take
+    /// priority over the unicode emoji.
+    pub fn with_custom_emoji(mut self, registry: &'a EmojiRegistry) -> Self {
+        self.custom_emoji = Some(registry);
+        self
+    }
+
+    /// the class of the `<span>` around spoilers. the default is `spoiler`.
+    pub fn with_spoiler_class(mut self, class: impl Into<String>) -> Self {
+        self.spoiler_class = Some(class.into());
+        self
+    }
+
+    /// passes each run of the user's text through `transformer`, for things like word filters or highlighting
+    pub fn with_transformer(mut self, transformer: impl TextTransformer + 'a) -> Self {
+        self.transformer = Some(Box::new(transformer));
+        self
+    }
+
+    /// wraps the matches of a `Query` in `<mark>`. `highlights` has to be from the document being rendered.
+    pub fn with_highlights(mut self, highlights: Highlights) -> Self {
+        self.highlights = Some(highlights);
+        self
+    }
+
+    /// the ids of the users mentioned so far, in order, without duplicates
+    pub fn mentions(&self) -> &[String] {
+        &self.mentions
+    }
+
+    /// returns the output of `writing_to`, or the first error it returned
+    pub fn finish_writing(mut self) -> Result<W, fmt::Error> {
+        self.flush();
+        self.output.result.map(|_| self.output.html)
+    }
+
+    // writes some html and records what it is. errors are kept by `Output`.
+    fn write(&mut self, kind: SegmentKind, write: impl FnOnce(&mut Output<W>) -> fmt::Result) {
+        self.flush();
+        let start = self.output.len;
+        let _ = write(&mut self.output);
+        self.record(kind, start, self.source.clone());
+    }
+
+    // transforms the text written since the last markup
+    fn flush(&mut self) {
+        let Some(transformer) = self.transformer.as_mut() else {
+            return;
+        };
+        if self.pending.is_empty() {
+            return;
+        }
+        let start = self.output.len;
+        transformer.transform(&self.pending, &mut HtmlOutput::new(&mut self.output));
+        self.pending.clear();
+        self.record(SegmentKind::Text, start, self.pending_source.clone());
+    }
+
+    // adds a segment for the html after `start`. neighbouring text and markup are combined.
+    fn record(&mut self, kind: SegmentKind, start: usize, input: Range<usize>) {
+        let output = start..self.output.len;
+        let Some(segments) = self.segments.as_mut() else {
+            return;
+        };
+        if self.link.is_some() || output.is_empty() {
+            return;
+        }
+
+        match segments.last_mut() {
+            Some(last)
+                if last.kind == kind && matches!(kind, SegmentKind::Text | SegmentKind::Markup) =>
+            {
+                last.input = last.input.start.min(input.start)..last.input.end.max(input.end);
+                last.output.end = output.end;
+            }
+            _ => segments.push(Segment {
+                kind,
+                input,
+                output,
+            }),
+        }
+    }
+
+    fn markup(&mut self, markup: &str) {
+        self.write(SegmentKind::Markup, |html| html.write_str(markup));
+    }
+
+    fn plain_text(&mut self, text: &str) {
+        if self.transformer.is_none() || self.autolink {
+            self.write(SegmentKind::Text, |html| write_escaped(text, html));
+            return;
+        }
+        // text which was split up by escapes is passed to the transformer in one piece
+        self.pending_source = match self.pending.is_empty() {
+            true => self.source.clone(),
+            false => self.pending_source.start..self.source.end,
+        };
+        self.pending += text;
+    }
+
+    // the parts of the node being rendered which should be highlighted
+    fn marks(&self, text: &str) -> Vec<Range<usize>> {
+        let marks = self
+            .highlights
+            .as_ref()
+            .and_then(|x| x.get(self.source.start))
+            .unwrap_or_default();
+        match marks.iter().all(|x| text.get(x.clone()).is_some()) {
+            true => marks.to_vec(),
+            false => Vec::new(),
+        }
+    }
+}
+
+impl<W: fmt::Write> Renderer for HtmlRenderer<'_, W> {
+    fn source(&mut self, span: Range<usize>) {
+        self.source = span;
+    }
+
+    fn text(&mut self, text: &str) {
+        let mut pos = 0;
+        for mark in self.marks(text) {
+            self.plain_text(&text[pos..mark.start]);
+            self.markup("<mark>");
+            self.plain_text(&text[mark.clone()]);
+            self.markup("</mark>");
+            pos = mark.end;
+        }
+        self.plain_text(&text[pos..]);
+    }
+
+    fn line_break(&mut self) {
+        self.write(SegmentKind::Text, |html| html.write_char('\n'));
+    }
+
+    fn code(&mut self, code: &str) {
+        let marks = self.marks(code);
+        self.write(SegmentKind::Code, |html| {
+            html.write_str("<code>")?;
+            escape_marked(code, &marks, html)?;
+            html.write_str("</code>")
+        });
+    }
+
+    fn mention(&mut self, name: &str) {
+        let Some(Mention::User { display_name, id }) = self.resolver.as_ref().map(|x| x(name))
+        else {
+            // not text the transformer should change
+            self.write(SegmentKind::Text, |html| {
+                html.write_char('@')?;
+                write_escaped(name, html)
+            });
+            return;
+        };
+
+        // the display name can have anything in it, so it isn't text
+        self.write(SegmentKind::Mention, |html| {
+            html.write_str("<span class=\"mention\" data-id=\"")?;
+            write_escaped(&id, html)?;
+            html.write_str("\" data-name=\"")?;
+            write_escaped(name, html)?;
+            html.write_str("\">@")?;
+            write_escaped(&display_name, html)?;
+            html.write_str("</span>")
+        });
+
+        if !self.mentions.contains(&id) {
+            self.mentions.push(id);
+        }
+    }
+
+    fn emoji(&mut self, name: &str) {
+        let src = self.custom_emoji.and_then(|x| x.get(name));
+        let Some(src) = src else {
+            match emoji::unicode_emoji(name) {
+                Some(emoji) => self.text(emoji),
+                None => {
+                    self.text(":");
+                    self.text(name);
+                    self.text(":");
+                }
+            }
+            return;
+        };
+
+        self.write(SegmentKind::Emoji, |html| {
+            html.write_str("<img class=\"emoji\" src=\"")?;
+            write_escaped(src, html)?;
+            html.write_str("\" alt=\":")?;
+            write_escaped(name, html)?;
+            html.write_str(":\">")
+        });
+    }
+
+    // this is specifically designed to work with prismjs
+    fn code_block(&mut self, language: Option<&str>, code: &str) {
+        let marks = self.marks(code);
+        self.write(SegmentKind::Code, |html| {
+            html.write_str("<pre><code class=\"language-")?;
+            write_escaped(language.unwrap_or("text"), html)?;
+            html.write_str("\">")?;
+            escape_marked(code, &marks, html)?;
+            html.write_str("</code></pre>")
+        });
+    }
+
+    fn start_heading(&mut self, level: u8) {
+        self.write(SegmentKind::Markup, |html| write!(html, "<h{level}>"));
+    }
+
+    fn end_heading(&mut self, level: u8) {
+        self.write(SegmentKind::Markup, |html| write!(html, "</h{level}>"));
+    }
+
+    fn start_block_quote(&mut self) {
+        self.markup("<blockquote>\n");
+        self.first_line = true;
+    }
+
+    fn end_block_quote(&mut self) {
+        self.markup("\n</blockquote>");
+    }
+
+    fn start_block_quote_line(&mut self) {
+        if !std::mem::take(&mut self.first_line) {
+            self.markup("\n");
+        }
+        self.markup("<p>");
+    }
+
+    fn end_block_quote_line(&mut self) {
+        self.markup("</p>");
+    }
+
+    fn start_list(&mut self, start: Option<u64>) {
+        match start {
+            None => self.markup("<ul>\n"),
+            Some(1) => self.markup("<ol>\n"),
+            Some(start) => self.write(SegmentKind::Markup, |html| {
+                writeln!(html, "<ol start=\"{start}\">")
+            }),
+        }
+        self.first_line = true;
+    }
+
+    fn end_list(&mut self, numbered: bool) {
+        self.markup(if numbered { "\n</ol>" } else { "\n</ul>" });
+    }
+
+    fn start_list_item(&mut self) {
+        if !std::mem::take(&mut self.first_line) {
+            self.markup("\n");
+        }
+        self.markup("<li>");
+    }
+
+    fn end_list_item(&mut self) {
+        self.markup("</li>");
+    }
+
+    fn start_emphasis(&mut self) {
+        self.markup("<em>");
+    }
+
+    fn end_emphasis(&mut self) {
+        self.markup("</em>");
+    }
+
+    fn start_strong(&mut self) {
+        self.markup("<strong>");
+    }
+
+    fn end_strong(&mut self) {
+        self.markup("</strong>");
+    }
+
+    fn start_underline(&mut self) {
+        self.markup("<u>");
+    }
+
+    fn end_underline(&mut self) {
+        self.markup("</u>");
+    }
+
+    fn start_strikethrough(&mut self) {
+        self.markup("<s>");
+    }
+
+    fn end_strikethrough(&mut self) {
+        self.markup("</s>");
+    }
+
+    fn start_spoiler(&mut self) {
+        let class = self.spoiler_class.clone();
+        self.write(SegmentKind::Markup, |html| {
+            html.write_str("<span class=\"")?;
+            write_escaped(class.as_deref().unwrap_or("spoiler"), html)?;
+            html.write_str("\">")
+        });
+    }
+
+    fn end_spoiler(&mut self) {
+        self.markup("</span>");
+    }
+
+    fn start_link(&mut self, url: &str) {
+        self.flush();
+        self.link = Some((self.output.len, self.source.start));
+        // a bare url has no markers
+        self.autolink = self.source.is_empty();
+        // the output keeps the first error, and ignores everything after it
+        let _ = self.output.write_str("<a href=\"");
+        let _ = write_escaped(url, &mut self.output);
+        let _ = self.output.write_str("\">");
+    }
+
+    fn end_link(&mut self) {
+        self.flush();
+        self.autolink = false;
+        let _ = self.output.write_str("</a>");
+        let segments = self.segments.as_mut();
+        if let Some(((start, input_start), segments)) = self.link.take().zip(segments) {
+            segments.push(Segment {
+                kind: SegmentKind::Link,
+                input: input_start..self.source.end,
+                output: start..self.output.len,
+            });
+        }
+    }
+}
+
+/// adds `text` to `builder`, escaped so it is only ever text. the html is shown as is, so anything from the user that
+/// could be read as html has to go through this. exported as `escape_html`.
+pub fn escape(text: &str, builder: &mut String) {
+    let _ = write_escaped(text, builder);
+}
+
+// writes the text in pieces, between the characters which need escaping. they are all ascii.
+pub(crate) fn write_escaped<W: fmt::Write + ?Sized>(text: &str, output: &mut W) -> fmt::Result {
+    let mut pos = 0;
+    for (idx, c) in text.bytes().enumerate() {
+        let escaped = match c {
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            b'&' => "&amp;",
+            b'"' => "&quot;",
+            b'\'' => "&#39;",
+            _ => continue,
+        };
+        output.write_str(&text[pos..idx])?;
+        output.write_str(escaped)?;
+        pos = idx + 1;
+    }
+    output.write_str(&text[pos..])
+}
+
+// true if the html for the text is the text itself, with the default options. this is conservative - a list needs
+// a space after the marker, but any line starting with one of the characters isn't plain.
+pub(crate) fn is_plain(text: &str) -> bool {
+    let is_marker = |c: char| "*_~|`[]<>&\"'\\#:".contains(c);
+    let is_list = |line: &str| {
+        line.trim_start()
+            .starts_with(|c: char| matches!(c, '-' | '+') || c.is_ascii_digit())
+    };
+    !text.contains(is_marker)
+        && !text.split('\n').any(is_list)
+        && !text
+            .as_bytes()
+            .windows(4)
+            .any(|x| x.eq_ignore_ascii_case(b"www."))
+}
+
+// escapes the text, and wraps the ranges in `<mark>`
+fn escape_marked(text: &str, marks: &[Range<usize>], output: &mut impl fmt::Write) -> fmt::Result {
+    let mut pos = 0;
+    for mark in marks {
+        write_escaped(&text[pos..mark.start], output)?;
+        output.write_str("<mark>")?;
+        write_escaped(&text[mark.clone()], output)?;
+        output.write_str("</mark>")?;
+        pos = mark.end;
+    }
+    write_escaped(&text[pos..], output)
+}
@@ -0,0 +1,149 @@
+// parses a message which is being typed or streamed in, one chunk at a time
+use std::ops::Range;
+
+use crate::{ast::Document, html::HtmlRenderer, options::Options, parser, render::Renderer};
+
+/// parses text as it is appended. everything up to the last blank line (outside of a code block) can't change, so
+/// those blocks are parsed once and kept. only the text after it is parsed again.
+///
+/// in provisional mode, unclosed markers on the last line are rendered as if they were closed, and so is a code
+/// block without its closing backticks. this stops a preview from flickering while `**bold` is being typed. once
+/// a line is finished, it is parsed the same way as `parse` would.
+#[derive(Clone, Debug, Default)]
+pub struct IncrementalParser {
+    options: Options,
+    provisional: bool,
+    text: String,
+    // the blocks for `text[..finished_len]`
+    finished: Document,
+    finished_len: usize,
+}
+
+impl IncrementalParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    pub fn provisional(mut self, provisional: bool) -> Self {
+        self.provisional = provisional;
+        self
+    }
+
+    /// adds text to the end of the message
+    pub fn push(&mut self, chunk: &str) {
+        self.text += chunk;
+        let pending = &self.text[self.finished_len..];
+        let len = parser::stable_len(pending, &self.options);
+        if len > 0 {
+            let (part, _) =
+                parser::parse_part(&pending[..len], self.finished_len, &self.options, false);
+            self.finished.blocks.extend(part.blocks);
+            self.finished_len += len;
+        }
+    }
+
+    /// the whole message so far
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// the blocks which won't change, no matter what is added
+    pub fn finished(&self) -> &Document {
+        &self.finished
+    }
+
+    pub fn document(&self) -> Document {
+        let mut document = self.finished.clone();
+        document.blocks.extend(self.pending().blocks);
+        document
+    }
+
+    pub fn render<R: Renderer + ?Sized>(&self, renderer: &mut R) {
+        self.finished.render(renderer);
+        self.pending().render(renderer);
+    }
+
+    /// renders the message the same way as `text_to_html`
+    pub fn to_html(&self) -> (String, Vec<Range<usize>>) {
+        let mut renderer = HtmlRenderer::new();
+        self.render(&mut renderer);
+        renderer.finish()
+    }
+
+    // the text after the finished blocks
+    fn pending(&self) -> Document {
+        parser::parse_part(
+            &self.text[self.finished_len..],
+            self.finished_len,
+            &self.options,
+            self.provisional,
+        )
+        .0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, text_to_html};
+
+    fn push_all(mut parser: IncrementalParser, chunks: &[&str]) -> IncrementalParser {
+        for chunk in chunks {
+            parser.push(chunk);
+        }
+        parser
+    }
+
+    #[test]
+    fn test_incremental() {
+        let chunks = [
+            "# ti",
+            "tle\n\n- a\n",
+            "\n```rust\nlet a",
+            " = 0;\n\nlet b = 1;",
+            "```\n\n**bo",
+            "ld**",
+        ];
+        let parser = push_all(IncrementalParser::new(), &chunks);
+        let text = chunks.concat();
+        assert_eq!(parser.to_html(), text_to_html(&text));
+        assert_eq!(parser.document().to_html(), parse(&text).to_html());
+        // everything before the last blank line is finished
+        assert_eq!(
+            parser.finished.blocks.last().map(|x| x.span.end),
+            text.rfind("\n\n").map(|x| x + 1)
+        );
+    }
+
+    #[test]
+    fn test_provisional() {
+        let parser = push_all(
+            IncrementalParser::new().provisional(true),
+            &["**a\n\nb **bold _it", "al"],
+        );
+        assert_eq!(
+            parser.to_html().0,
+            "**a\n\nb <strong>bold <em>ital</em></strong>"
+        );
+
+        let parser = push_all(
+            IncrementalParser::new().provisional(true),
+            &["`code", " [link", "\n```rust\nlet a"],
+        );
+        assert_eq!(
+            parser.to_html().0,
+            "`code [link\n<pre><code class=\"language-rust\">let a</code></pre>"
+        );
+
+        // without provisional mode, the markers are text until they are closed
+        let parser = push_all(IncrementalParser::new(), &["**bold\n```rust\nlet a"]);
+        assert_eq!(parser.to_html().0, "**bold\n```rust\nlet a");
+    }
+}
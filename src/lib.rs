@@ -2,473 +2,145 @@
 /// text to html, with support for the following
 /// - italics
 /// - bold
+/// - underline, for the discord dialect
 /// - strikethrough
+/// - spoilers
 /// - code
 /// - multiline code
 /// - multiline code with a language
+/// - headings
+/// - block quotes
+/// - lists
+/// - links, and bare urls
+/// - mentions
+/// - emoji shortcodes, and optionally emoticons. custom emoji can be rendered with `HtmlRenderer`.
 ///
-// for the devs - some markdown is parsed in StackEntry::to_string() - the headings and emojis
-use std::{collections::VecDeque, ops::Range};
-
-// returns the converted text and a list of indices which point to sections that aren't code blocks
-pub fn text_to_html(text: &str) -> (String, Vec<Range<usize>>) {
-    let mut stack: VecDeque<StackEntry> = VecDeque::new();
-    stack.push_back(Markdown::Line.into());
-
-    // empty tags or just whitespace are not allowed
-    let convert_html = |stack: &mut VecDeque<StackEntry>, tag: &str, md: Markdown| {
-        let entry = match stack.pop_back() {
-            Some(x) => x,
-            None => {
-                // should never happen
-                stack.push_back(md.into());
-                return;
-            }
-        };
-
-        if std::mem::discriminant(&md) != std::mem::discriminant(&entry.md) {
-            // if there was an error and the 2 were not the same type, put it back.
-            stack.push_back(entry);
-            stack.push_back(md.into());
-            return;
-        }
+/// the text is first parsed into a `Document`, which can be inspected or transformed before being rendered.
+/// spans and ranges are byte offsets, and always fall on utf-8 character boundaries.
+/// `text_to_plain` removes the markup instead. other output formats can be made by implementing `Renderer`.
+///
+/// parsing and rendering take time linear in the length of the text, for any input. `Options::max_depth` limits how
+/// many markers can be open at once, which keeps the search for an opening marker short, and `Options::max_len`
+/// limits how much of the text is parsed at all.
+// for the devs - parser.rs builds the Document, render.rs walks it, and html.rs and plain.rs are the renderers.
+use std::{borrow::Cow, fmt, io, ops::Range};
+
+mod ast;
+mod diagnostics;
+mod emoji;
+mod highlight;
+mod html;
+mod incremental;
+mod links;
+mod mentions;
+mod options;
+mod parser;
+mod plain;
+mod render;
+mod segments;
+mod transform;
+mod truncate;
+
+pub use ast::{Block, BlockKind, Document, Inline, InlineKind, ListItem};
+pub use diagnostics::{Diagnostic, DiagnosticKind};
+pub use emoji::{unicode_emoji, EmojiRegistry};
+pub use highlight::{Highlights, Query};
+pub use html::{escape as escape_html, HtmlRenderer};
+pub use incremental::IncrementalParser;
+pub use mentions::Mention;
+pub use options::{Dialect, Options};
+pub use plain::PlainRenderer;
+pub use render::Renderer;
+pub use segments::{Segment, SegmentKind};
+pub use transform::{HtmlOutput, TextTransformer};
+
+/// parses the text into a tree of blocks and inline elements
+pub fn parse(text: &str) -> Document {
+    parse_with(text, &Options::default())
+}
 
-        if entry.text.trim().is_empty() {
-            stack.push_back(StackEntry::new(
-                Markdown::Line,
-                format!("{}{}", md.to_string(), entry.text),
-            ));
-            stack.push_back(md.into());
-        } else {
-            // this is specifically designed to work with prismjs. doing this string parsing stuff
-            // simplifies the state machine a lot.
-            // get the text and either append it to the previous element or push it on the stack.
-            let text = match tag {
-                "language" => {
-                    let default = ("text".to_string(), entry.text.clone());
-                    let (language, text) = match entry.text.find('\n') {
-                        Some(x) => {
-                            let before = entry.text[0..x].to_string();
-                            let after: String = entry.text.chars().skip(x + 1).collect();
-                            match before.trim() {
-                                x if !x.is_empty() => (x.to_string(), after),
-                                _ => default,
-                            }
-                        }
-                        None => match entry.text.find(' ') {
-                            Some(x) => {
-                                let before = entry.text[0..x].to_string();
-                                let after: String = entry.text.chars().skip(x + 1).collect();
-                                match before.trim() {
-                                    x if !x.is_empty() => (x.to_string(), after),
-                                    _ => default,
-                                }
-                            }
-                            None => default,
-                        },
-                    };
-                    format!(
-                        "<pre><code class=\"language-{language}\">{}</code></pre>",
-                        text.trim()
-                    )
-                }
-                "code" => {
-                    format!(
-                        "<pre><code class=\"language-text\">{}</code></pre>",
-                        entry.text.trim()
-                    )
-                }
-                _ => format!("<{tag}>{}</{tag}>", entry.text.trim()),
-            };
-
-            if matches!(tag, "language" | "code") {
-                stack.push_back(StackEntry::new(Markdown::Code, text));
-                // to get a list of indices that aren't code, can't have non-code being appended to a code segment.
-                stack.push_back(Markdown::Line.into());
-            } else if let Some(to_append) = stack.back_mut() {
-                to_append.text += &text;
-            } else {
-                // should never happen
-                stack.push_back(StackEntry::new(Markdown::Line, text));
-            }
-        }
-    };
-
-    let prev_matches = |stack: &VecDeque<StackEntry>, md: Markdown| {
-        stack.back().map(|x| x.md == md).unwrap_or_default()
-    };
-
-    // fold back of stack into previous entry
-    let fold_prev = |stack: &mut VecDeque<StackEntry>| {
-        let text = stack.pop_back().map(|x| x.to_string()).unwrap_or_default();
-        if let Some(entry) = stack.back_mut() {
-            entry.text += &text;
-        } else {
-            // should never happen
-            stack.push_back(StackEntry::new(Markdown::Line, text));
-        }
-    };
-
-    for char in text.chars() {
-        let (prev_md, prev_empty) = stack
-            .back()
-            .map(|x| (x.md.clone(), x.text.is_empty()))
-            .expect("stack should not be empty");
-
-        match char {
-            '*' => match prev_md {
-                Markdown::Star => {
-                    if prev_empty {
-                        stack.pop_back();
-                        if prev_matches(&stack, Markdown::DoubleStar) {
-                            // handle double star
-                            convert_html(&mut stack, "strong", Markdown::DoubleStar);
-                        } else {
-                            stack.push_back(Markdown::DoubleStar.into());
-                        }
-                    } else {
-                        // handle star
-                        convert_html(&mut stack, "em", Markdown::Star);
-                    }
-                }
-                _ => stack.push_back(Markdown::Star.into()),
-            },
-            '_' => match prev_md {
-                Markdown::Underscore => {
-                    if prev_empty {
-                        stack.pop_back();
-                        if prev_matches(&stack, Markdown::DoubleUnderscore) {
-                            // handle double underscore
-                            convert_html(&mut stack, "strong", Markdown::DoubleUnderscore);
-                        } else {
-                            stack.push_back(Markdown::DoubleUnderscore.into());
-                        }
-                    } else {
-                        // handle underscore
-                        convert_html(&mut stack, "em", Markdown::Underscore);
-                    }
-                }
-                _ => stack.push_back(Markdown::Underscore.into()),
-            },
-            '`' => match prev_md {
-                Markdown::Backtick => {
-                    if prev_empty {
-                        stack.pop_back();
-                        stack.push_back(Markdown::DoubleBacktick.into());
-                    } else {
-                        // handle backtick
-                        convert_html(&mut stack, "code", Markdown::Backtick);
-                    }
-                }
-                Markdown::DoubleBacktick => {
-                    if prev_empty {
-                        stack.pop_back();
-                        if prev_matches(&stack, Markdown::TripleBacktick) {
-                            // handle triple backtick
-                            convert_html(&mut stack, "language", Markdown::TripleBacktick);
-                        } else {
-                            stack.push_back(Markdown::TripleBacktick.into());
-                        }
-                    } else {
-                        // the pattern looks like this: ``[\w+]`. Make a code segment.
-                        let text = stack.pop_back().map(|x| x.text).unwrap_or_default();
-                        let code =
-                            format!("<pre><code class=\"language-text\">{text}</code></pre>");
-                        if let Some(entry) = stack.back_mut() {
-                            entry.text.push('`');
-                        } else {
-                            // should never happen
-                            stack.push_back(StackEntry::new(Markdown::Line, String::from('`')));
-                        }
-                        stack.push_back(StackEntry::new(Markdown::Code, code));
-                        stack.push_back(Markdown::Line.into());
-                    }
-                }
-                _ => stack.push_back(Markdown::Backtick.into()),
-            },
-            '~' => match prev_md {
-                Markdown::Tilde => {
-                    if prev_empty {
-                        // now have a double tilde. but is the previous one a double tilde?
-                        stack.pop_back();
-                        if prev_matches(&stack, Markdown::DoubleTilde) {
-                            convert_html(&mut stack, "s", Markdown::DoubleTilde);
-                        } else {
-                            stack.push_back(Markdown::DoubleTilde.into());
-                        }
-                    } else {
-                        // single tilde means nothing. merge last 2 entries on stack and push new entry afterwards.
-                        fold_prev(&mut stack);
-                        stack.push_back(Markdown::Tilde.into());
-                    }
-                }
-                _ => {
-                    stack.push_back(Markdown::Tilde.into());
-                }
-            },
-            '#' => match prev_md {
-                Markdown::Line => {
-                    if prev_empty {
-                        stack.pop_back();
-                        stack.push_back(Markdown::H1.into());
-                    } else if let Some(entry) = stack.back_mut() {
-                        entry.text.push('#');
-                    }
-                }
-                Markdown::H1 => {
-                    if prev_empty {
-                        stack.pop_back();
-                        stack.push_back(Markdown::H2.into());
-                    } else if let Some(entry) = stack.back_mut() {
-                        entry.text.push('#');
-                    }
-                }
-                Markdown::H2 => {
-                    if prev_empty {
-                        stack.pop_back();
-                        stack.push_back(Markdown::H3.into());
-                    } else if let Some(entry) = stack.back_mut() {
-                        entry.text.push('#');
-                    }
-                }
-                Markdown::H3 => {
-                    if prev_empty {
-                        stack.pop_back();
-                        stack.push_back(Markdown::H4.into());
-                    } else if let Some(entry) = stack.back_mut() {
-                        entry.text.push('#');
-                    }
-                }
-                Markdown::H4 => {
-                    if prev_empty {
-                        stack.pop_back();
-                        stack.push_back(Markdown::H5.into());
-                    } else if let Some(entry) = stack.back_mut() {
-                        entry.text.push('#');
-                    }
-                }
-                Markdown::H5 => {
-                    if let Some(entry) = stack.back_mut() {
-                        entry.text.push('#');
-                    }
-                }
-                _ => stack.push_back(Markdown::H1.into()),
-            },
-            '\n' => match prev_md {
-                Markdown::TripleBacktick => {
-                    if let Some(entry) = stack.back_mut() {
-                        entry.text.push('\n');
-                    }
-                }
-                _ => {
-                    stack.push_back(Markdown::NewLine.into());
-                }
-            },
-            '>' if matches!(prev_md, Markdown::NewLine | Markdown::Line) && prev_empty => {
-                stack.push_back(Markdown::GreaterThan.into());
-            }
-            ' ' if matches!(prev_md, Markdown::GreaterThan) && prev_empty => {
-                // replace the "> " with a BlockQuote
-                stack.pop_back();
-
-                // if prev was a newline and the one before that was a block quote, get rid of the newline
-                if stack
-                    .back()
-                    .map(|x| matches!(x.md, Markdown::NewLine) && x.text.is_empty())
-                    .unwrap_or_default()
-                {
-                    if let Some(prev2) = stack.pop_back() {
-                        // if it wasn't a block quote, put the prev entry back
-                        if !stack
-                            .back()
-                            .map(|x| matches!(x.md, Markdown::BlockQuote))
-                            .unwrap_or_default()
-                        {
-                            stack.push_back(prev2);
-                        }
-                    }
-                }
+/// like `parse`, but only the markdown enabled in `options` is parsed
+pub fn parse_with(text: &str, options: &Options) -> Document {
+    parser::parse(text, options).0
+}
 
-                stack.push_back(Markdown::BlockQuote.into());
-            }
-            c => {
-                if let Some(entry) = stack.back_mut() {
-                    entry.text.push(c);
-                } else {
-                    // should never happen
-                    stack.push_back(StackEntry::new(Markdown::Line, String::from(c)));
-                }
-            }
-        }
-    }
+/// like `parse_with`, but also returns the markdown which looks like a mistake - markers which were never closed,
+/// empty pairs of markers, and headings or block quotes without a space. these are all rendered as text.
+pub fn parse_with_diagnostics(text: &str, options: &Options) -> (Document, Vec<Diagnostic>) {
+    parser::parse(text, options)
+}
 
-    let mut builder = String::new();
-
-    // want something like this:
-    // > line 1
-    // > line 2
-    // to be in a single blockquote tag. So the state machine removed newlines between otherwise successive block quotes.
-    // now all that's left is to use a stack to combine them.
-    //
-    // this approach works for adding a single new feature (in this case block quotes). But who knows if this can be
-    // extended to multiple features, such as different types of lists.
-    let mut block_quote_combiner = VecDeque::<String>::new();
-    let add_block_quote = |block_quote_combiner: &mut VecDeque<String>, builder: &mut String| {
-        if let Some(first) = block_quote_combiner.pop_front() {
-            let mut block_quote_inner = format!("<p>{}</p>", first.trim());
-            while let Some(next) = block_quote_combiner.pop_front() {
-                block_quote_inner += &format!("\n<p>{}</p>", next.trim())
-            }
-            let block_quote_entry = StackEntry::new(Markdown::BlockQuote, block_quote_inner);
-            *builder += &block_quote_entry.to_string();
-        }
-    };
-
-    // return a vec of indices that don't contain code blocks
-    let mut indices: Vec<Range<usize>> = Vec::new();
-    let mut start_idx: Option<usize> = None;
-    while let Some(entry) = stack.pop_front() {
-        if start_idx.is_none() && !matches!(entry.md, Markdown::Code) && !entry.text.is_empty() {
-            start_idx.replace(builder.len());
-        }
-        match entry.md {
-            Markdown::Code => {
-                if let Some(start) = start_idx.take() {
-                    indices.push(start..builder.len());
-                }
-                builder += &entry.to_string();
-            }
-            Markdown::BlockQuote => block_quote_combiner.push_back(entry.text),
-            _ => {
-                add_block_quote(&mut block_quote_combiner, &mut builder);
-                builder += &entry.to_string();
-            }
-        }
-    }
-    add_block_quote(&mut block_quote_combiner, &mut builder);
-    if let Some(start) = start_idx.take() {
-        indices.push(start..builder.len());
-    }
-    (builder, indices)
+// returns the converted text and a list of indices which point to sections that aren't code blocks or links
+pub fn text_to_html(text: &str) -> (String, Vec<Range<usize>>) {
+    text_to_html_with(text, &Options::default())
 }
 
-#[derive(Clone, Eq, PartialEq)]
-enum Markdown {
-    // a line of text
-    Line,
-    NewLine,
-    // italics
-    Star,
-    // bold
-    DoubleStar,
-    // italics
-    Underscore,
-    // bold
-    DoubleUnderscore,
-    // code
-    Backtick,
-    // nothing
-    DoubleBacktick,
-    // multiline code
-    TripleBacktick,
-    // nothing
-    Tilde,
-    // strikethrough
-    DoubleTilde,
-    // octothorpe becomes heading
-    H1,
-    // double octothorpe
-    H2,
-    // 3x octothorpe
-    H3,
-    // 4x octothorpe
-    H4,
-    // 5x octothorpe
-    H5,
-    // block quote
-    GreaterThan,
-    BlockQuote,
-
-    // don't do emoji replacement here
-    Code,
+/// like `text_to_html`, but returns what each part of the html is instead of the ranges. only `SegmentKind::Text`
+/// is text written by the user - mentions, urls, code and tags are separate.
+pub fn text_to_html_segments(text: &str) -> (String, Vec<Segment>) {
+    parse(text).to_html_segments()
 }
 
-impl ToString for Markdown {
-    fn to_string(&self) -> String {
-        match self {
-            Markdown::Line | Markdown::Code | Markdown::BlockQuote => String::new(),
-            Markdown::NewLine => String::from("\n"),
-            Markdown::Star => String::from("*"),
-            Markdown::DoubleStar => String::from("**"),
-            Markdown::Underscore => String::from("_"),
-            Markdown::DoubleUnderscore => String::from("__"),
-            Markdown::Backtick => String::from("`"),
-            Markdown::DoubleBacktick => String::from("``"),
-            Markdown::TripleBacktick => String::from("```"),
-            Markdown::Tilde => String::from("~"),
-            Markdown::DoubleTilde => String::from("~~"),
-            Markdown::H1 => String::from("#"),
-            Markdown::H2 => String::from("##"),
-            Markdown::H3 => String::from("###"),
-            Markdown::H4 => String::from("####"),
-            Markdown::H5 => String::from("#####"),
-            Markdown::GreaterThan => String::from(">"),
-        }
-    }
+/// like `text_to_html`, but the words in `query` are wrapped in `<mark>`, for search results
+pub fn text_to_html_highlighted(text: &str, query: &Query) -> (String, Vec<Range<usize>>) {
+    let document = parse(text);
+    let mut renderer = HtmlRenderer::new().with_highlights(query.find(&document));
+    document.render(&mut renderer);
+    renderer.finish()
 }
 
-struct StackEntry {
-    md: Markdown,
-    text: String,
+/// like `text_to_html`, but the html is written to `output` as it is rendered
+pub fn write_html(text: &str, output: &mut impl fmt::Write) -> fmt::Result {
+    parse(text).write_html(output)
 }
 
-impl ToString for StackEntry {
-    fn to_string(&self) -> String {
-        let get_heading_text = |tag: &str| {
-            // a heading needs at least 2 characters - one space and one character for the title.
-            if self.text.len() < 2 {
-                return self.md.to_string() + &self.text;
-            }
+/// like `write_html`, for files and sockets
+pub fn write_html_io(text: &str, output: &mut impl io::Write) -> io::Result<()> {
+    parse(text).write_html_io(output)
+}
 
-            let first = self.text[0..1].to_string();
-            let second = &self.text[1..].trim();
+/// like `text_to_html`, without the ranges. text without any markdown or characters which need escaping is
+/// returned as is, without allocating.
+pub fn text_to_html_cow(text: &str) -> Cow<'_, str> {
+    match html::is_plain(text) {
+        true => Cow::Borrowed(text),
+        false => Cow::Owned(text_to_html(text).0),
+    }
+}
 
-            if first.trim().is_empty() && !second.is_empty() {
-                format!("<{tag}>{second}</{tag}>")
-            } else {
-                self.md.to_string() + &self.text
-            }
-        };
+/// like `text_to_html`, but disabled markdown is left as text
+pub fn text_to_html_with(text: &str, options: &Options) -> (String, Vec<Range<usize>>) {
+    parse_with(text, options).to_html()
+}
 
-        match self.md {
-            Markdown::H1 => get_heading_text("h1"),
-            Markdown::H2 => get_heading_text("h2"),
-            Markdown::H3 => get_heading_text("h3"),
-            Markdown::H4 => get_heading_text("h4"),
-            Markdown::H5 => get_heading_text("h5"),
-            Markdown::BlockQuote => {
-                format!("<blockquote>\n{}\n</blockquote>", self.text)
-            }
-            // simplify the state machine
-            Markdown::Code => self.md.to_string() + &self.text,
-            _ => self.md.to_string() + &self.text,
-        }
-    }
+/// returns the text without any markup, for notifications and previews. spoilers are replaced with `[spoiler]` -
+/// use `PlainRenderer::show_spoilers` to show them.
+pub fn text_to_plain(text: &str) -> String {
+    parse(text).to_plain()
 }
 
-impl From<Markdown> for StackEntry {
-    fn from(value: Markdown) -> Self {
-        Self {
-            md: value,
-            text: String::new(),
-        }
-    }
+/// like `text_to_html`, but only the first `max` visible characters are kept, followed by `ellipsis` if anything
+/// was cut. see `Document::truncate`.
+pub fn text_to_html_truncated(
+    text: &str,
+    max: usize,
+    ellipsis: &str,
+) -> (String, Vec<Range<usize>>) {
+    parse(text).truncate(max, ellipsis).to_html()
 }
 
-impl StackEntry {
-    fn new(md: Markdown, text: String) -> Self {
-        Self { md, text }
-    }
+/// like `text_to_html`, but mentions are looked up with `resolver`. also returns the ids of the mentioned users,
+/// without duplicates.
+pub fn text_to_html_with_mentions(
+    text: &str,
+    options: &Options,
+    resolver: impl Fn(&str) -> Mention,
+) -> (String, Vec<Range<usize>>, Vec<String>) {
+    let mut renderer = HtmlRenderer::new().with_mentions(resolver);
+    parse_with(text, options).render(&mut renderer);
+    let ids = renderer.mentions().to_vec();
+    let (html, ranges) = renderer.finish();
+    (html, ranges, ids)
 }
 
 #[cfg(test)]
@@ -487,6 +159,39 @@ mod tests {
         assert_eq!(text_to_html(test_str).0.as_str(), test_str);
     }
 
+    #[test]
+    fn test_borrowed() {
+        assert!(matches!(
+            text_to_html_cow("hello world.\nbye"),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(text_to_html_cow("1. a"), Cow::Owned(_)));
+        assert!(matches!(text_to_html_cow("see www.a.com"), Cow::Owned(_)));
+        assert!(matches!(text_to_html_cow("see WwW.a.com"), Cow::Owned(_)));
+        assert_eq!(text_to_html_cow("a & *b*"), "a &amp; <em>b</em>");
+    }
+
+    #[test]
+    fn test_write_html() {
+        let test_str = "# a\n> b\n```rust let c```";
+        let mut html = String::new();
+        write_html(test_str, &mut html).unwrap();
+        assert_eq!(html, text_to_html(test_str).0);
+
+        // the renderer stops at the first error, and returns it
+        struct Full;
+        impl io::Write for Full {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::WriteZero.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let error = write_html_io(test_str, &mut Full).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+    }
+
     #[test]
     fn test_star() {
         let test_str = "*hello world*";
@@ -515,6 +220,75 @@ mod tests {
         assert_eq!(text_to_html(test_str).0.as_str(), expected);
     }
 
+    #[test]
+    fn test_intraword_underscore() {
+        let test_str = "snake_case_variable_name and 2_000_000";
+        assert_eq!(text_to_html(test_str).0.as_str(), test_str);
+    }
+
+    #[test]
+    fn test_bold_italic() {
+        let test_str = "***bold italic*** **a *b***";
+        let expected = "<em><strong>bold italic</strong></em> <strong>a <em>b</em></strong>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    // examples from https://spec.commonmark.org/0.31.2/#emphasis-and-strong-emphasis
+    #[test]
+    fn test_commonmark_emphasis() {
+        let examples = [
+            ("*foo bar*", "<em>foo bar</em>"),
+            ("a * foo bar*", "a * foo bar*"),
+            ("foo*bar*", "foo<em>bar</em>"),
+            ("5*6*78", "5<em>6</em>78"),
+            ("_foo bar_", "<em>foo bar</em>"),
+            ("_ foo bar_", "_ foo bar_"),
+            ("foo_bar_", "foo_bar_"),
+            ("5_6_78", "5_6_78"),
+            ("пристаням_стремятся_", "пристаням_стремятся_"),
+            // a decomposed é ends with a combining accent, which is part of the word
+            ("cafe\u{301}_menu_", "cafe\u{301}_menu_"),
+            ("café_menu_", "café_menu_"),
+            ("foo-_(bar)_", "foo-<em>(bar)</em>"),
+            ("*foo bar *", "*foo bar *"),
+            ("*(*foo)", "*(*foo)"),
+            ("*(*foo*)*", "<em>(<em>foo</em>)</em>"),
+            ("*foo*bar", "<em>foo</em>bar"),
+            ("_foo_bar", "_foo_bar"),
+            ("_foo_bar_baz_", "<em>foo_bar_baz</em>"),
+            ("_(bar)_.", "<em>(bar)</em>."),
+            ("**foo bar**", "<strong>foo bar</strong>"),
+            ("** foo bar**", "** foo bar**"),
+            ("foo**bar**", "foo<strong>bar</strong>"),
+            ("__foo__bar", "__foo__bar"),
+            (
+                "__foo, __bar__, baz__",
+                "<strong>foo, <strong>bar</strong>, baz</strong>",
+            ),
+            ("*foo**bar**baz*", "<em>foo<strong>bar</strong>baz</em>"),
+            ("*foo**bar*", "<em>foo**bar</em>"),
+            ("***foo** bar*", "<em><strong>foo</strong> bar</em>"),
+            ("*foo **bar***", "<em>foo <strong>bar</strong></em>"),
+            ("foo***bar***baz", "foo<em><strong>bar</strong></em>baz"),
+            (
+                "foo******bar*********baz",
+                "foo<strong><strong><strong>bar</strong></strong></strong>***baz",
+            ),
+            ("**foo*", "*<em>foo</em>"),
+            ("*foo**", "<em>foo</em>*"),
+            ("***foo**", "*<strong>foo</strong>"),
+            (
+                "_____foo_____",
+                "<em><strong><strong>foo</strong></strong></em>",
+            ),
+            ("*foo _bar* baz_", "<em>foo _bar</em> baz_"),
+            ("**foo**bar", "<strong>foo</strong>bar"),
+        ];
+        for (test_str, expected) in examples {
+            assert_eq!(text_to_html(test_str).0, expected, "{test_str}");
+        }
+    }
+
     #[test]
     fn test_double_tilde() {
         let test_str = "~~hello world~~";
@@ -525,10 +299,19 @@ mod tests {
     #[test]
     fn test_backtick() {
         let test_str = "`hello world`";
-        let expected = "<pre><code class=\"language-text\">hello world</code></pre>";
+        let expected = "<code>hello world</code>";
         assert_eq!(text_to_html(test_str).0.as_str(), expected);
     }
 
+    #[test]
+    fn test_backtick_inline() {
+        let test_str = "run `cargo test` before pushing";
+        let expected = "run <code>cargo test</code> before pushing";
+        let (transformed, indices) = text_to_html(test_str);
+        assert_eq!(transformed, expected);
+        assert_eq!(indices, vec![0..4, 27..expected.len()]);
+    }
+
     #[test]
     fn test_language1() {
         let test_str = "```rust hello world```";
@@ -655,10 +438,62 @@ mod tests {
         assert_eq!(text_to_html(test_str).0.as_str(), expected);
     }
 
+    #[test]
+    fn test_unordered_list() {
+        let test_str = "- a\n* *b*\n+ c";
+        let expected = "<ul>\n<li>a</li>\n<li><em>b</em></li>\n<li>c</li>\n</ul>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_ordered_list() {
+        let test_str = "1. a\n2) b";
+        let expected = "<ol>\n<li>a</li>\n<li>b</li>\n</ol>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+
+        let test_str = "3. a\n4. b";
+        let expected = "<ol start=\"3\">\n<li>a</li>\n<li>b</li>\n</ol>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_nested_list() {
+        let test_str = "- a\n  - b\n    1. c\n  - d\n- e";
+        let expected = "<ul>\n<li>a<ul>\n<li>b<ol>\n<li>c</li>\n</ol></li>\n<li>d</li>\n</ul></li>\n<li>e</li>\n</ul>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_list_and_block_quote() {
+        let test_str = "text\n- a\n> q1\n> q2\n- b\n\nmore";
+        let expected = "text\n<ul>\n<li>a</li>\n</ul>\n<blockquote>\n<p>q1</p>\n<p>q2</p>\n</blockquote>\n<ul>\n<li>b</li>\n</ul>\n\nmore";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_switching_lists() {
+        let test_str = "- a\n1. b";
+        let expected = "<ul>\n<li>a</li>\n</ul>\n<ol>\n<li>b</li>\n</ol>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+
+        // a nested list is closed too
+        let test_str = "- a\n  - b\n1. c";
+        let expected = "<ul>\n<li>a<ul>\n<li>b</li>\n</ul></li>\n</ul>\n<ol>\n<li>c</li>\n</ol>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_not_a_list() {
+        let test_str = "-5 degrees\n1.5 million\n- \n**bold** *a*\n1234567890. b";
+        let expected =
+            "-5 degrees\n1.5 million\n- \n<strong>bold</strong> <em>a</em>\n1234567890. b";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
     #[test]
     fn test_failed_block_quote() {
         let test_str = ">should not be blockquote";
-        let expected = ">should not be blockquote";
+        let expected = "&gt;should not be blockquote";
         assert_eq!(text_to_html(test_str).0.as_str(), expected);
     }
 
@@ -687,16 +522,24 @@ mod tests {
     #[test]
     fn test_partial1() {
         let test_str = "hello world ``h`ello **world** ~hello world";
-        let expected = "hello world `<pre><code class=\"language-text\">h</code></pre>ello <strong>world</strong> ~hello world";
+        let expected = "hello world `<code>h</code>ello <strong>world</strong> ~hello world";
         let (transformed, indices) = text_to_html(test_str);
         assert_eq!(transformed, expected);
-        assert_eq!(indices, vec![0..13, 60..expected.len()]);
+        assert_eq!(indices, vec![0..13, 27..expected.len()]);
     }
 
     #[test]
     fn test_empty_star() {
+        // at the start of a line, "* " is a list item
         let test_str = "* * *test*";
-        let expected = "* * <em>test</em>";
+        let expected = "<ul>\n<li>* <em>test</em></li>\n</ul>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_empty_star_mid_line() {
+        let test_str = "a * * *test*";
+        let expected = "a * * <em>test</em>";
         assert_eq!(text_to_html(test_str).0.as_str(), expected);
     }
 
@@ -724,7 +567,7 @@ mod tests {
     #[test]
     fn test_empty_backtick() {
         let test_str = "` ` `test`";
-        let expected = "` ` <pre><code class=\"language-text\">test</code></pre>";
+        let expected = "` ` <code>test</code>";
         assert_eq!(text_to_html(test_str).0.as_str(), expected);
     }
 
@@ -752,4 +595,649 @@ mod tests {
         let expected = ":)";
         assert_eq!(text_to_html(test_str).0.as_str(), expected);
     }
+
+    #[test]
+    fn test_escape_emphasis() {
+        let test_str = r"2\*3\*4 snake\_case\_name \*\*not bold\*\*";
+        let expected = "2*3*4 snake_case_name **not bold**";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_code() {
+        let test_str = r"\`not code\` \`\`\`not a block\`\`\`";
+        let expected = "`not code` ```not a block```";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+
+        // backslashes are text inside of code
+        let test_str = r"`a\` b";
+        let expected = "<code>a\\</code> b";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_tilde() {
+        let test_str = r"\~~not struck~~";
+        let expected = "~~not struck~~";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_heading() {
+        let test_str = r"\# not a heading";
+        let expected = "# not a heading";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_block_quote() {
+        let test_str = "a\n\\> not a quote";
+        let expected = "a\n&gt; not a quote";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escaped_backslash() {
+        let test_str = r"\\*emphasis* C:\dir \a";
+        let expected = r"\<em>emphasis</em> C:\dir \a";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_link() {
+        let test_str = "see [the **docs**](https://docs.rs/a_(b)) or [mail](mailto:a@b.com)";
+        let expected = "see <a href=\"https://docs.rs/a_(b)\">the <strong>docs</strong></a> or <a href=\"mailto:a@b.com\">mail</a>";
+        let (html, ranges) = text_to_html(test_str);
+        assert_eq!(html, expected);
+        assert_eq!(ranges, vec![0..4, 65..69]);
+    }
+
+    #[test]
+    fn test_not_a_link() {
+        let test_str = "[a] (b) [](https://a.com) [c](d e) [*f](https://f.com)";
+        let expected = "[a] (b) [](https://a.com) [c](d e) <a href=\"https://f.com\">*f</a>";
+        assert_eq!(text_to_html(test_str).0, expected);
+    }
+
+    #[test]
+    fn test_autolink() {
+        let test_str =
+            "go to https://a.com/b_c_d, _www.e.com_ or http://f.com:/g.\nnothttps://h.com";
+        let expected = "go to <a href=\"https://a.com/b_c_d\">https://a.com/b_c_d</a>, <em><a href=\"https://www.e.com\">www.e.com</a></em> or <a href=\"http://f.com:/g\">http://f.com:/g</a>.\nnothttps://h.com";
+        let (html, ranges) = text_to_html(test_str);
+        assert_eq!(html, expected);
+        // the :/ in the urls can't be turned into emojis
+        let text: Vec<&str> = ranges.into_iter().map(|x| &html[x]).collect();
+        assert_eq!(
+            text,
+            vec!["go to ", ", <em>", "</em> or ", ".\nnothttps://h.com"]
+        );
+
+        // the angle brackets around a url aren't part of it
+        let test_str = "<https://a.com>";
+        let expected = "&lt;<a href=\"https://a.com\">https://a.com</a>&gt;";
+        assert_eq!(text_to_html(test_str).0, expected);
+    }
+
+    #[test]
+    fn test_autolink_in_code() {
+        let test_str = "`https://a.com` [https://b.com](https://c.com)";
+        let expected = "<code>https://a.com</code> <a href=\"https://c.com\">https://b.com</a>";
+        assert_eq!(text_to_html(test_str).0, expected);
+    }
+
+    fn resolve(name: &str) -> Mention {
+        match name {
+            "bob" | "did:key:z6Mkbob" => Mention::User {
+                display_name: "Bob".into(),
+                id: "1".into(),
+            },
+            "alice" => Mention::User {
+                display_name: "Alice".into(),
+                id: "2".into(),
+            },
+            _ => Mention::Unknown,
+        }
+    }
+
+    #[test]
+    fn test_mentions() {
+        let test_str = "hi @bob, @did:key:z6Mkbob and **@alice**. @carol a@bob.com";
+        let (html, ranges, ids) =
+            text_to_html_with_mentions(test_str, &Options::default(), resolve);
+        let bob = "<span class=\"mention\" data-id=\"1\" data-name=\"bob\">@Bob</span>";
+        let did = "<span class=\"mention\" data-id=\"1\" data-name=\"did:key:z6Mkbob\">@Bob</span>";
+        let alice = "<span class=\"mention\" data-id=\"2\" data-name=\"alice\">@Alice</span>";
+        let expected = format!("hi {bob}, {did} and <strong>{alice}</strong>. @carol a@bob.com");
+        assert_eq!(html, expected);
+        assert_eq!(ids, vec!["1", "2"]);
+        let text: Vec<&str> = ranges.into_iter().map(|x| &html[x]).collect();
+        assert_eq!(
+            text,
+            vec!["hi ", ", ", " and <strong>", "</strong>. @carol a@bob.com"]
+        );
+    }
+
+    #[test]
+    fn test_mentions_in_code() {
+        let test_str = "`@bob` ```\n@bob``` [@bob](https://a.com)";
+        let (html, _, ids) = text_to_html_with_mentions(test_str, &Options::default(), resolve);
+        let expected = "<code>@bob</code> <pre><code class=\"language-text\">@bob</code></pre> <a href=\"https://a.com\">@bob</a>";
+        assert_eq!(html, expected);
+        assert!(ids.is_empty());
+    }
+
+    #[test]
+    fn test_mentions_without_resolver() {
+        let test_str = "@bob @did:key:z6Mkbob";
+        assert_eq!(text_to_html(test_str).0, test_str);
+    }
+
+    #[test]
+    fn test_shortcodes() {
+        let test_str = ":smile: :+1::tada: *:not_an_emoji:* `:smile:` 10:30:00";
+        let expected = "😄 👍🎉 <em>:not_an_emoji:</em> <code>:smile:</code> 10:30:00";
+        assert_eq!(text_to_html(test_str).0, expected);
+    }
+
+    #[test]
+    fn test_custom_emoji() {
+        let mut registry = EmojiRegistry::new();
+        registry.insert("party_parrot", "https://a.com/parrot.gif");
+        let mut renderer = HtmlRenderer::new().with_custom_emoji(&registry);
+        parse("hi :party_parrot: :smile: :unknown: `:party_parrot:`").render(&mut renderer);
+        let (html, ranges) = renderer.finish();
+        let expected = "hi <img class=\"emoji\" src=\"https://a.com/parrot.gif\" alt=\":party_parrot:\"> 😄 :unknown: <code>:party_parrot:</code>";
+        assert_eq!(html, expected);
+        let text: Vec<&str> = ranges.into_iter().map(|x| &html[x]).collect();
+        assert_eq!(text, vec!["hi ", " 😄 :unknown: "]);
+    }
+
+    #[test]
+    fn test_spoiler() {
+        let test_str = "||hello|| __hello *||world ~~world~~||*__ ~~||a~~|| |";
+        let expected = "<span class=\"spoiler\">hello</span> <strong>hello <em><span class=\"spoiler\">world <s>world</s></span></em></strong> ~~||a~~|| |";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+        assert_eq!(text_to_html("|| || a|b").0.as_str(), "|| || a|b");
+
+        let mut renderer = HtmlRenderer::new().with_spoiler_class("blur");
+        parse("||a||").render(&mut renderer);
+        assert_eq!(renderer.finish().0, "<span class=\"blur\">a</span>");
+    }
+}
+
+#[cfg(test)]
+mod xss_tests {
+    use super::*;
+
+    #[test]
+    fn test_script_tag() {
+        let test_str = "<script>alert(1)</script>";
+        let expected = "&lt;script&gt;alert(1)&lt;/script&gt;";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_img_onerror() {
+        let test_str = "<img src=x onerror=\"alert('x')\">";
+        let expected = "&lt;img src=x onerror=&quot;alert(&#39;x&#39;)&quot;&gt;";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_ampersand() {
+        let test_str = "fish & chips &amp;";
+        let expected = "fish &amp; chips &amp;amp;";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_in_emphasis() {
+        let test_str = "**<b>bold</b>** _<i>_";
+        let expected = "<strong>&lt;b&gt;bold&lt;/b&gt;</strong> <em>&lt;i&gt;</em>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_in_code() {
+        let test_str = "`<script>` ```html <p>hi</p>```";
+        let expected = "<code>&lt;script&gt;</code> <pre><code class=\"language-html\">&lt;p&gt;hi&lt;/p&gt;</code></pre>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_language() {
+        let test_str = "```\"><script>\nalert(1)```";
+        let expected =
+            "<pre><code class=\"language-&quot;&gt;&lt;script&gt;\">alert(1)</code></pre>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_in_heading() {
+        let test_str = "# <h1>title";
+        let expected = "<h1>&lt;h1&gt;title</h1>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_in_block_quote() {
+        let test_str = "> <iframe>\n>> nested";
+        let expected = "<blockquote>\n<p>&lt;iframe&gt;</p>\n</blockquote>\n&gt;&gt; nested";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_link_schemes() {
+        let test_str = "[a](javascript:alert(1)) [b](JaVaScRiPt:alert(1)) [c](data:text/html,x) [d](vbscript:x)";
+        let expected = "[a](javascript:alert(1)) [b](JaVaScRiPt:alert(1)) [c](data:text/html,x) [d](vbscript:x)";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_in_link() {
+        let test_str = "[<b>](https://a.com/\"onclick=\"x) https://b.com/'x";
+        let expected = "<a href=\"https://a.com/&quot;onclick=&quot;x\">&lt;b&gt;</a> <a href=\"https://b.com/&#39;x\">https://b.com/&#39;x</a>";
+        assert_eq!(text_to_html(test_str).0.as_str(), expected);
+    }
+
+    #[test]
+    fn test_escape_in_mention() {
+        let resolve = |_: &str| Mention::User {
+            display_name: "<b>\"".into(),
+            id: "\"><script>".into(),
+        };
+        let test_str = "@bob";
+        let expected = "<span class=\"mention\" data-id=\"&quot;&gt;&lt;script&gt;\" data-name=\"bob\">@&lt;b&gt;&quot;</span>";
+        let (html, _, _) = text_to_html_with_mentions(test_str, &Options::default(), resolve);
+        assert_eq!(html, expected);
+    }
+
+    #[test]
+    fn test_escape_in_custom_emoji() {
+        let mut registry = EmojiRegistry::new();
+        registry.insert("x", "\" onerror=\"alert(1)");
+        let mut renderer = HtmlRenderer::new().with_custom_emoji(&registry);
+        parse(":x:").render(&mut renderer);
+        let expected = "<img class=\"emoji\" src=\"&quot; onerror=&quot;alert(1)\" alt=\":x:\">";
+        assert_eq!(renderer.finish().0, expected);
+    }
+}
+
+#[cfg(test)]
+mod unicode_tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // markdown markers mixed with multibyte text, so markers end up next to emoji, CJK and combining marks
+    fn markdown_text() -> impl Strategy<Value = String> {
+        let pieces = prop::sample::select(vec![
+            "*",
+            "**",
+            "_",
+            "__",
+            "`",
+            "``",
+            "```",
+            "~",
+            "~~",
+            "|",
+            "||",
+            "#",
+            "# ",
+            "##### ",
+            "> ",
+            ">",
+            "\\",
+            "\n",
+            "- ",
+            "1. ",
+            "[",
+            "](",
+            ")",
+            "https://",
+            "www.",
+            "@",
+            "did:key:",
+            ":",
+            ":smile:",
+            "  ",
+            "\t",
+            " ",
+            "a",
+            "é",
+            "e\u{301}",
+            "😀",
+            "👩‍👩‍👧",
+            "漢字",
+            "\u{3000}",
+            "\u{200d}",
+            "ß",
+            "İ",
+        ]);
+        prop::collection::vec(pieces, 0..64).prop_map(|x| x.concat())
+    }
+
+    fn check_spans(text: &str, inlines: &[Inline]) {
+        for inline in inlines {
+            assert!(text.is_char_boundary(inline.span.start));
+            assert!(text.is_char_boundary(inline.span.end));
+            match &inline.kind {
+                InlineKind::Emphasis(content)
+                | InlineKind::Strong(content)
+                | InlineKind::Underline(content)
+                | InlineKind::Strikethrough(content)
+                | InlineKind::Spoiler(content)
+                | InlineKind::Link { content, .. } => check_spans(text, content),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_blocks(text: &str, blocks: &[Block]) {
+        for block in blocks {
+            assert!(text.is_char_boundary(block.span.start));
+            assert!(text.is_char_boundary(block.span.end));
+            match &block.kind {
+                BlockKind::Paragraph(content) | BlockKind::Heading { content, .. } => {
+                    check_spans(text, content)
+                }
+                BlockKind::BlockQuote(lines) => lines.iter().for_each(|x| check_spans(text, x)),
+                BlockKind::CodeBlock { .. } => {}
+                BlockKind::List { items, .. } => {
+                    for item in items {
+                        assert!(text.is_char_boundary(item.span.start));
+                        assert!(text.is_char_boundary(item.span.end));
+                        check_spans(text, &item.content);
+                        check_blocks(text, &item.children);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_document(text: &str) {
+        let (document, diagnostics) = parse_with_diagnostics(text, &Options::default());
+        check_blocks(text, &document.blocks);
+        for diagnostic in diagnostics {
+            assert!(text.get(diagnostic.span).is_some());
+        }
+
+        let (html, ranges) = document.to_html();
+        for range in ranges {
+            assert!(html.is_char_boundary(range.start));
+            assert!(html.is_char_boundary(range.end));
+        }
+
+        // the segments cover the html in order, and point at the markdown
+        let (_, segments) = document.to_html_segments();
+        let mut end = 0;
+        for segment in segments {
+            assert_eq!(segment.output.start, end);
+            assert!(html.get(segment.output.clone()).is_some());
+            assert!(text.get(segment.input).is_some());
+            end = segment.output.end;
+        }
+        assert_eq!(end, html.len());
+
+        // highlighting never splits a character
+        let query = Query::new("a é *").in_code(true);
+        let mut renderer = HtmlRenderer::new().with_highlights(query.find(&document));
+        document.render(&mut renderer);
+        renderer.finish();
+    }
+
+    #[test]
+    fn test_multibyte_heading() {
+        assert_eq!(text_to_html("#é").0, "#é");
+        assert_eq!(text_to_html("# é").0, "<h1>é</h1>");
+        assert_eq!(text_to_html("#\u{3000}漢字").0, "<h1>漢字</h1>");
+        assert_eq!(text_to_html("##😀").0, "##😀");
+    }
+
+    #[test]
+    fn test_multibyte_language() {
+        let test_str = "```rust\nlet é = \"😀\";```";
+        let expected = "<pre><code class=\"language-rust\">let é = &quot;😀&quot;;</code></pre>";
+        assert_eq!(text_to_html(test_str).0, expected);
+
+        let test_str = "```日本語 コード```";
+        let expected = "<pre><code class=\"language-日本語\">コード</code></pre>";
+        assert_eq!(text_to_html(test_str).0, expected);
+    }
+
+    #[test]
+    fn test_multibyte_emphasis() {
+        let test_str = "*é*__漢字__~~👩‍👩‍👧~~`ß`";
+        let expected = "<em>é</em><strong>漢字</strong><s>👩‍👩‍👧</s><code>ß</code>";
+        assert_eq!(text_to_html(test_str).0, expected);
+    }
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_text(text in any::<String>()) {
+            check_document(&text);
+        }
+
+        #[test]
+        fn test_arbitrary_markdown(text in markdown_text()) {
+            check_document(&text);
+            text_to_html_with(&text, &Options::none());
+        }
+
+        #[test]
+        fn test_arbitrary_truncation(text in markdown_text(), max in 0..40usize) {
+            let document = parse(&text).truncate(max, "…");
+            check_blocks(&text, &document.blocks);
+            // nothing is added, other than the ellipsis
+            let (plain, full) = (document.to_plain(), parse(&text).to_plain());
+            prop_assert!(plain.len() <= full.len() + "…".len());
+        }
+
+        #[test]
+        fn test_incremental_matches(text in markdown_text()) {
+            // one character at a time, so every possible chunk boundary is hit
+            let mut parser = IncrementalParser::new();
+            let mut provisional = IncrementalParser::new().provisional(true);
+            for c in text.chars() {
+                parser.push(c.encode_utf8(&mut [0; 4]));
+                provisional.push(c.encode_utf8(&mut [0; 4]));
+                check_blocks(provisional.text(), &provisional.document().blocks);
+            }
+            prop_assert_eq!(parser.to_html(), text_to_html(&text));
+        }
+
+        #[test]
+        fn test_plain_text_is_unchanged(text in "[^*_`~|#>\\\\<>&\"'\\-+.):]*") {
+            // without markdown, list markers, urls or characters that need escaping, the text is returned as is
+            prop_assert_eq!(text_to_html(&text).0, text);
+        }
+
+        #[test]
+        fn test_streamed_html(text in markdown_text()) {
+            let html = text_to_html(&text).0;
+            let mut streamed = String::new();
+            write_html(&text, &mut streamed).unwrap();
+            prop_assert_eq!(&streamed, &html);
+            let mut bytes = Vec::new();
+            write_html_io(&text, &mut bytes).unwrap();
+            prop_assert_eq!(bytes, html.as_bytes());
+            prop_assert_eq!(text_to_html_cow(&text), html);
+        }
+
+        #[test]
+        fn test_borrowed_html(text in any::<String>()) {
+            if let Cow::Borrowed(html) = text_to_html_cow(&text) {
+                prop_assert_eq!(html, text_to_html(&text).0);
+            }
+        }
+    }
+}
+
+// messages come from other users, so no input should be able to make parsing slow
+#[cfg(test)]
+mod adversarial_tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // renders `input(n)` for two sizes. timing is noisy, so the bound is loose - with 8 times the input, a quadratic
+    // parser would take 64 times as long.
+    fn assert_linear(options: &Options, input: impl Fn(usize) -> String) {
+        assert_linear_with(|text| drop(text_to_html_with(text, options)), input);
+    }
+
+    fn assert_linear_with(render: impl Fn(&str), input: impl Fn(usize) -> String) {
+        let time = |n: usize| {
+            let text = input(n);
+            (0..3)
+                .map(|_| {
+                    let start = Instant::now();
+                    render(&text);
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or_default()
+        };
+        let (small, large) = (time(1_000), time(8_000));
+        assert!(
+            large < small * 24 + Duration::from_millis(20),
+            "{small:?} then {large:?} for {:?}",
+            input(2)
+        );
+    }
+
+    fn unlimited() -> Options {
+        Options {
+            max_len: usize::MAX,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_markers() {
+        let options = unlimited();
+        for pattern in [
+            "*_~", "*a ", "*a **b ", "_a __b ", "~~a ||b ", "**_", "*a*b_c_",
+        ] {
+            assert_linear(&options, |n| pattern.repeat(n));
+        }
+        assert_linear(&options, |n| "*a ".repeat(n) + "b*");
+        assert_linear(&options, |n| "a*".repeat(n) + &"*b".repeat(n));
+        // every `*` looks through the open underscores for a star
+        assert_linear(&options, |n| "_a ".repeat(n) + &"b*".repeat(n));
+    }
+
+    #[test]
+    fn test_highlights() {
+        let query = Query::new("a");
+        assert_linear_with(
+            |text| drop(text_to_html_highlighted(text, &query)),
+            |n| "a *a* ".repeat(n),
+        );
+    }
+
+    #[test]
+    fn test_code() {
+        let options = unlimited();
+        for pattern in ["`a", "``a`", "```", "\\```", "a```b", "``"] {
+            assert_linear(&options, |n| pattern.repeat(n));
+        }
+    }
+
+    #[test]
+    fn test_links() {
+        let options = unlimited();
+        for pattern in ["[a](", "[", "](", "[a](b c", "[a](https://a.com", "(", ")"] {
+            assert_linear(&options, |n| pattern.repeat(n) + "](https://a.com)");
+        }
+        assert_linear(&options, |n| "[a](".to_string() + &"(".repeat(n));
+        // the link text already has a link in it, so each `](` fails
+        assert_linear(&options, |n| {
+            "[[a](https://a.com)".to_string() + &"*b*](https://a.com)".repeat(n)
+        });
+
+        // urls which aren't allowed are skipped
+        let options = Options {
+            link_schemes: vec!["https".into()],
+            ..unlimited()
+        };
+        for pattern in ["http://", "http://a.com/(", "www.a"] {
+            assert_linear(&options, |n| pattern.repeat(n));
+        }
+    }
+
+    #[test]
+    fn test_mentions_and_emoji() {
+        let options = Options {
+            emoticons: true,
+            ..unlimited()
+        };
+        for pattern in ["@", "@a.", "@did:key:", ":a", "::", ":smile", "<3<", ":)"] {
+            assert_linear(&options, |n| pattern.repeat(n));
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        let options = unlimited();
+        for pattern in ["> ", ">", "# ", "- ", "1. ", "\n", "- a\n", "> a\n"] {
+            assert_linear(&options, |n| pattern.repeat(n));
+        }
+        // lists which keep getting more indented
+        assert_linear(&options, |n| {
+            (0..n).map(|x| " ".repeat(x % 64) + "- a\n").collect()
+        });
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let options = Options {
+            max_depth: 2,
+            ..Default::default()
+        };
+        let test_str = "*a _b ~~c~~ d_ e*";
+        let expected = "<em>a <em>b ~~c~~ d</em> e</em>";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+
+        let test_str = "- a\n  - b\n    - c";
+        let expected = "<ul>\n<li>a<ul>\n<li>b</li>\n<li>c</li>\n</ul></li>\n</ul>";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+
+        // without a limit, the depth is still capped, so deeply nested markers and lists don't overflow the stack
+        // when they are rendered
+        let options = Options {
+            max_depth: usize::MAX,
+            ..unlimited()
+        };
+        let markers = "*a _b ".repeat(10_000) + &"c_ d* ".repeat(10_000);
+        let lists: String = (0..1_000).map(|x| "  ".repeat(x) + "- a\n").collect();
+        let query = Query::new("a");
+        for test_str in [markers, lists] {
+            let document = parse_with(&test_str, &options);
+            let mut renderer = HtmlRenderer::new().with_highlights(query.find(&document));
+            document.render(&mut renderer);
+            renderer.finish();
+            document.to_plain();
+            document.truncate(10, "…");
+        }
+    }
+
+    #[test]
+    fn test_max_len() {
+        let options = Options {
+            max_len: 6,
+            ..Default::default()
+        };
+        let test_str = "**a** **b**\n# c";
+        let expected = "<strong>a</strong> **b**\n# c";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+
+        // the limit isn't in the middle of a character
+        let test_str = "**é**";
+        let options = Options {
+            max_len: 3,
+            ..Default::default()
+        };
+        assert_eq!(text_to_html_with(test_str, &options).0, test_str);
+    }
 }
@@ -0,0 +1,105 @@
+// urls in `[text](url)` links, and bare urls in the text
+use crate::options::Options;
+
+// the scheme of an absolute url, like `https` in `https://example.com`
+fn scheme(url: &str) -> Option<&str> {
+    let (scheme, _) = url.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+// returns what goes in the href, or None if the url isn't allowed. relative urls are not allowed, so the only way
+// to get a link is with one of the schemes from the options - `javascript:` and friends can't sneak through.
+pub(crate) fn href(url: &str, options: &Options) -> Option<String> {
+    let is_allowed = |scheme: &str| {
+        options
+            .link_schemes
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(scheme))
+    };
+
+    if starts_with_ignore_case(url, "www.") {
+        return is_allowed("https").then(|| format!("https://{url}"));
+    }
+    scheme(url)
+        .filter(|x| is_allowed(x))
+        .map(|_| url.to_string())
+}
+
+// returns the length of the url at the start of the text, if there is one
+pub(crate) fn autolink_len(text: &str) -> Option<usize> {
+    let prefix = ["https://", "http://", "www."]
+        .into_iter()
+        .find(|x| starts_with_ignore_case(text, x))?;
+
+    let end = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>'))
+        .unwrap_or(text.len());
+    let mut url = &text[..end];
+
+    // punctuation at the end is probably part of the sentence, not the url. a closing paren is kept if it has
+    // an opening one, for links like https://en.wikipedia.org/wiki/Rust_(programming_language)
+    let opened = url.matches('(').count();
+    let mut closed = url.matches(')').count();
+    while let Some(c) = url.chars().next_back() {
+        match c {
+            '?' | '!' | '.' | ',' | ':' | ';' | '*' | '_' | '~' | '\'' | '"' => {}
+            ')' if closed > opened => closed -= 1,
+            _ => break,
+        }
+        url = &url[..url.len() - c.len_utf8()];
+    }
+
+    (url.len() > prefix.len()).then_some(url.len())
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .map(|x| x.eq_ignore_ascii_case(prefix))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_href() {
+        let options = Options::default();
+        assert_eq!(
+            href("https://a.com", &options).as_deref(),
+            Some("https://a.com")
+        );
+        assert_eq!(
+            href("mailto:a@b.com", &options).as_deref(),
+            Some("mailto:a@b.com")
+        );
+        assert_eq!(
+            href("www.a.com", &options).as_deref(),
+            Some("https://www.a.com")
+        );
+        assert_eq!(
+            href("HTTP://a.com", &options).as_deref(),
+            Some("HTTP://a.com")
+        );
+        assert_eq!(href("javascript:alert(1)", &options), None);
+        assert_eq!(href("JavaScript:alert(1)", &options), None);
+        assert_eq!(href("data:text/html,x", &options), None);
+        assert_eq!(href("vbscript:x", &options), None);
+        assert_eq!(href("/relative", &options), None);
+        assert_eq!(href("java\tscript:x", &options), None);
+    }
+
+    #[test]
+    fn test_autolink_len() {
+        assert_eq!(autolink_len("https://a.com b"), Some(13));
+        assert_eq!(autolink_len("www.a.com."), Some(9));
+        assert_eq!(autolink_len("https://a.com/b_(c)),"), Some(19));
+        assert_eq!(autolink_len("https://"), None);
+        assert_eq!(autolink_len("wwwa.com"), None);
+        assert_eq!(autolink_len("https://é.com<"), Some(14));
+        assert_eq!(autolink_len("https://a.com>"), Some(13));
+    }
+}
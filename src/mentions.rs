@@ -0,0 +1,53 @@
+// `@username` and `@did:method:id`
+
+/// what a mention refers to. returned by the resolver passed to `HtmlRenderer::with_mentions`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mention {
+    User {
+        display_name: String,
+        id: String,
+    },
+    /// the mention is left as text
+    Unknown,
+}
+
+// `text` starts after the `@`. returns the length of the name or did.
+pub(crate) fn mention_len(text: &str) -> Option<usize> {
+    let len = did_len(text).unwrap_or_else(|| {
+        text.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '-')))
+            .unwrap_or(text.len())
+    });
+    // a period at the end is probably the end of the sentence
+    let len = text[..len].trim_end_matches('.').len();
+    (len > 0).then_some(len)
+}
+
+// did:method:id. the method is lowercase letters and numbers.
+fn did_len(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix("did:")?;
+    let method = rest
+        .find(|c: char| !(c.is_ascii_lowercase() || c.is_ascii_digit()))
+        .unwrap_or(rest.len());
+    let id = rest[method..].strip_prefix(':').filter(|_| method > 0)?;
+    let id_len = id
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | ':' | '%' | '-')))
+        .unwrap_or(id.len());
+    let id_len = id[..id_len].trim_end_matches([':', '.']).len();
+    (id_len > 0).then_some(4 + method + 1 + id_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mention_len() {
+        assert_eq!(mention_len("bob, hi"), Some(3));
+        assert_eq!(mention_len("bob_1.x."), Some(7));
+        assert_eq!(mention_len("élise"), Some(6));
+        assert_eq!(mention_len("did:key:z6Mk.9: hi"), Some(14));
+        assert_eq!(mention_len("did:KEY:z6Mk"), Some(3));
+        assert_eq!(mention_len(" bob"), None);
+        assert_eq!(mention_len("."), None);
+    }
+}
@@ -0,0 +1,322 @@
+// which markdown is parsed. a disabled marker is left in the text as is.
+
+/// every syntax is enabled by default. use `Options::none()` and turn on only what is needed for things like
+/// usernames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// `*x*`
+    pub star_emphasis: bool,
+    /// `_x_`
+    pub underscore_emphasis: bool,
+    /// `**x**`
+    pub star_strong: bool,
+    /// `__x__`
+    pub underscore_strong: bool,
+    /// `__x__` is underlined instead of bold, like discord. this takes priority over `underscore_strong`.
+    pub underscore_underline: bool,
+    /// `~~x~~`
+    pub strikethrough: bool,
+    /// `~x~` is strikethrough too, like github. needs `strikethrough`.
+    pub single_tilde: bool,
+    /// `||x||`
+    pub spoilers: bool,
+    /// `` `x` ``
+    pub inline_code: bool,
+    /// ```` ```x``` ````
+    pub code_blocks: bool,
+    /// the first word (or line) of a code block is the language. when disabled, the whole block is code.
+    pub code_block_language: bool,
+    /// `# x` through `##### x`. `headings[0]` is `h1`.
+    pub headings: [bool; 5],
+    /// `> x`
+    pub block_quotes: bool,
+    /// `- x`, `* x` and `+ x`
+    pub unordered_lists: bool,
+    /// `1. x` and `1) x`
+    pub ordered_lists: bool,
+    /// `\*` is a literal `*`. works for any ascii punctuation.
+    pub escapes: bool,
+    /// a single newline between two lines of text is a space, like commonmark. a blank line still breaks the line.
+    pub soft_breaks: bool,
+    /// `[x](https://example.com)`
+    pub links: bool,
+    /// `https://example.com`, `http://example.com` and `www.example.com` become links
+    pub autolinks: bool,
+    /// links are only made for urls with one of these schemes. urls without a scheme (other than `www.`) are never
+    /// links, so things like `javascript:` can't get through.
+    pub link_schemes: Vec<String>,
+    /// `@name` and `@did:method:id`. see `HtmlRenderer::with_mentions`.
+    pub mentions: bool,
+    /// `:smile:`. unknown names are left as text.
+    pub shortcodes: bool,
+    /// `:)`, `:(` and `<3`. off by default.
+    pub emoticons: bool,
+    /// how many markers can be open at once, like the 3 in `*a **b ~~c`, and how deeply lists can be nested.
+    /// markers past this are text, and more indented list items join the deepest list. the default is 32, and it
+    /// can't be more than 128 - the renderers are recursive, so a deeper tree could overflow the stack.
+    pub max_depth: usize,
+    /// markdown is only parsed in the first `max_len` bytes. the rest is text. the default is 64 KiB.
+    pub max_len: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            star_emphasis: true,
+            underscore_emphasis: true,
+            star_strong: true,
+            underscore_strong: true,
+            underscore_underline: false,
+            strikethrough: true,
+            single_tilde: false,
+            spoilers: true,
+            inline_code: true,
+            code_blocks: true,
+            code_block_language: true,
+            headings: [true; 5],
+            block_quotes: true,
+            unordered_lists: true,
+            ordered_lists: true,
+            escapes: true,
+            soft_breaks: false,
+            links: true,
+            autolinks: true,
+            link_schemes: vec!["http".into(), "https".into(), "mailto".into()],
+            mentions: true,
+            shortcodes: true,
+            emoticons: false,
+            max_depth: 32,
+            max_len: 64 * 1024,
+        }
+    }
+}
+
+/// what people coming from other apps expect markdown to do. each one is a set of `Options`, which can be changed
+/// further.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// everything this crate supports. the same as `Options::default()`.
+    #[default]
+    Markdowns,
+    /// no strikethrough, spoilers, bare urls, mentions or emoji, and single newlines are spaces
+    CommonMark,
+    /// `~x~` is strikethrough too, there are no spoilers, and single newlines are spaces. github's tables aren't
+    /// supported yet, so they are left as text.
+    GitHub,
+    /// `__x__` is underlined and only `#` through `###` are headings
+    Discord,
+}
+
+impl Dialect {
+    pub fn options(self) -> Options {
+        let default = Options::default();
+        match self {
+            Dialect::Markdowns => default,
+            Dialect::CommonMark => Options {
+                strikethrough: false,
+                spoilers: false,
+                autolinks: false,
+                mentions: false,
+                shortcodes: false,
+                soft_breaks: true,
+                ..default
+            },
+            Dialect::GitHub => Options {
+                single_tilde: true,
+                spoilers: false,
+                soft_breaks: true,
+                ..default
+            },
+            Dialect::Discord => Options {
+                underscore_underline: true,
+                headings: [true, true, true, false, false],
+                ..default
+            },
+        }
+    }
+}
+
+impl From<Dialect> for Options {
+    fn from(dialect: Dialect) -> Self {
+        dialect.options()
+    }
+}
+
+impl Options {
+    /// plain text - nothing is parsed
+    pub fn none() -> Self {
+        Self {
+            star_emphasis: false,
+            underscore_emphasis: false,
+            star_strong: false,
+            underscore_strong: false,
+            underscore_underline: false,
+            strikethrough: false,
+            single_tilde: false,
+            spoilers: false,
+            inline_code: false,
+            code_blocks: false,
+            code_block_language: false,
+            headings: [false; 5],
+            block_quotes: false,
+            unordered_lists: false,
+            ordered_lists: false,
+            escapes: false,
+            soft_breaks: false,
+            links: false,
+            autolinks: false,
+            link_schemes: Self::default().link_schemes,
+            mentions: false,
+            shortcodes: false,
+            emoticons: false,
+            max_depth: Self::default().max_depth,
+            max_len: Self::default().max_len,
+        }
+    }
+
+    // `max_depth`, capped so the tree can always be rendered
+    pub(crate) fn depth(&self) -> usize {
+        self.max_depth.min(128)
+    }
+
+    pub(crate) fn heading(&self, level: usize) -> bool {
+        level
+            .checked_sub(1)
+            .and_then(|x| self.headings.get(x))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_to_html_with;
+
+    #[test]
+    fn test_none() {
+        let test_str = "# *a* __b__ ~~c~~ `d` ```rust e```\n> f";
+        let expected = "# *a* __b__ ~~c~~ `d` ```rust e```\n&gt; f";
+        assert_eq!(text_to_html_with(test_str, &Options::none()).0, expected);
+    }
+
+    #[test]
+    fn test_emphasis_only() {
+        let options = Options {
+            star_emphasis: true,
+            ..Options::none()
+        };
+        let test_str = "*a* **b** _c_";
+        let expected = "<em>a</em> **b** _c_";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+    }
+
+    #[test]
+    fn test_strong_without_emphasis() {
+        let options = Options {
+            star_emphasis: false,
+            underscore_emphasis: false,
+            ..Default::default()
+        };
+        let test_str = "*a* **b** _c_ __d__ *e**f**";
+        let expected = "*a* <strong>b</strong> _c_ <strong>d</strong> *e<strong>f</strong>";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+    }
+
+    #[test]
+    fn test_headings() {
+        let options = Options {
+            headings: [false, true, true, true, true],
+            ..Default::default()
+        };
+        let test_str = "# a\n## b";
+        let expected = "# a\n<h2>b</h2>";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+    }
+
+    #[test]
+    fn test_code() {
+        let options = Options {
+            code_blocks: false,
+            ..Default::default()
+        };
+        let test_str = "```rust a``` `b`";
+        let expected = "```rust a``` <code>b</code>";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+
+        let options = Options {
+            code_block_language: false,
+            ..Default::default()
+        };
+        let test_str = "```rust a```";
+        let expected = "<pre><code class=\"language-text\">rust a</code></pre>";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+    }
+
+    #[test]
+    fn test_link_schemes() {
+        let options = Options {
+            link_schemes: vec!["https".into()],
+            ..Default::default()
+        };
+        let test_str = "[a](http://a.com) [b](https://b.com) http://c.com";
+        let expected = "[a](http://a.com) <a href=\"https://b.com\">b</a> http://c.com";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+
+        let options = Options {
+            autolinks: false,
+            ..Default::default()
+        };
+        let test_str = "https://a.com";
+        assert_eq!(text_to_html_with(test_str, &options).0, test_str);
+    }
+
+    #[test]
+    fn test_dialects() {
+        let test_str = "__a__ ~b~ ~~c~~ ||d|| @e\nf\n\n#### g";
+        let expected = [
+            (
+                Dialect::Markdowns,
+                "<strong>a</strong> ~b~ <s>c</s> <span class=\"spoiler\">d</span> @e\nf\n\n<h4>g</h4>",
+            ),
+            (
+                Dialect::CommonMark,
+                "<strong>a</strong> ~b~ ~~c~~ ||d|| @e f\n\n<h4>g</h4>",
+            ),
+            (
+                Dialect::GitHub,
+                "<strong>a</strong> <s>b</s> <s>c</s> ||d|| @e f\n\n<h4>g</h4>",
+            ),
+            (
+                Dialect::Discord,
+                "<u>a</u> ~b~ <s>c</s> <span class=\"spoiler\">d</span> @e\nf\n\n#### g",
+            ),
+        ];
+        for (dialect, expected) in expected {
+            assert_eq!(text_to_html_with(test_str, &dialect.into()).0, expected);
+        }
+
+        let test_str = "___a___ __b *c*__";
+        let expected = "<em><u>a</u></em> <u>b <em>c</em></u>";
+        let options = Dialect::Discord.options();
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+    }
+
+    #[test]
+    fn test_emoticons() {
+        let options = Options {
+            emoticons: true,
+            ..Default::default()
+        };
+        let test_str = "hi :) :( <3 :)x `:)`";
+        let expected = "hi 🙂 🙁 ❤️ :)x <code>:)</code>";
+        assert_eq!(text_to_html_with(test_str, &options).0, expected);
+
+        let options = Options {
+            shortcodes: false,
+            ..Default::default()
+        };
+        let test_str = ":smile:";
+        assert_eq!(text_to_html_with(test_str, &options).0, test_str);
+    }
+}
@@ -0,0 +1,1483 @@
+// text -> Document
+//
+// parsing happens in 3 steps:
+// - triple backtick code blocks are found first. they can span multiple lines and can start in the middle of a line.
+// - the text between code blocks is split into lines, which become headings, block quotes, or paragraph text.
+// - the text of each line is run through a state machine which matches up the inline markers. inline markdown
+//   never spans multiple lines.
+//
+// every node remembers the byte range of the text it came from. the offsets are passed down with each slice of the text.
+use std::{collections::HashMap, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    ast::{Block, BlockKind, Document, Inline, InlineKind, ListItem},
+    diagnostics::{Diagnostic, DiagnosticKind},
+    emoji, links, mentions,
+    options::Options,
+};
+
+pub(crate) fn parse(text: &str, options: &Options) -> (Document, Vec<Diagnostic>) {
+    parse_part(text, 0, options, false)
+}
+
+// parses a piece of a message which starts at `offset`. in provisional mode, the message is still being written -
+// markers on the last line which haven't been closed yet, and a code block without closing backticks, are treated
+// as if they were closed.
+pub(crate) fn parse_part(
+    text: &str,
+    offset: usize,
+    options: &Options,
+    provisional: bool,
+) -> (Document, Vec<Diagnostic>) {
+    // markdown past the limit is text
+    let limit = options.max_len.saturating_sub(offset);
+    if text.len() > limit {
+        let limit = (0..=limit)
+            .rev()
+            .find(|x| text.is_char_boundary(*x))
+            .unwrap_or_default();
+        let (mut document, diagnostics) = parse_part(&text[..limit], offset, options, provisional);
+        let plain = Options {
+            max_len: usize::MAX,
+            ..Options::none()
+        };
+        let (rest, _) = parse_part(&text[limit..], offset + limit, &plain, false);
+        document.blocks.extend(rest.blocks);
+        return (document, diagnostics);
+    }
+
+    let last_line = text.rfind('\n').map(|x| x + 1).unwrap_or_default();
+    let mut builder = BlockBuilder::new(options);
+    builder.provisional_from = provisional.then_some(offset + last_line);
+
+    let (segments, unclosed) = segments(text, options, provisional);
+    if let Some(open) = unclosed.filter(|_| !provisional) {
+        let span = offset + open..offset + open + 3;
+        builder
+            .diagnostics
+            .push(Diagnostic::new(DiagnosticKind::Unclosed, span));
+    }
+    for segment in segments {
+        match segment {
+            Segment::Text(span) => builder.add_text(&text[span.clone()], offset + span.start),
+            Segment::Code(span) => builder.add_code_block(
+                &text[span.start + 3..span.end - 3],
+                self::offset(span, offset),
+            ),
+            Segment::UnclosedCode(span) => {
+                builder.add_code_block(&text[span.start + 3..], self::offset(span, offset))
+            }
+        }
+    }
+    let (document, mut diagnostics) = builder.finish();
+    diagnostics.sort_by_key(|x| x.span.start);
+    (document, diagnostics)
+}
+
+// the length of the start of the text which won't change when more text is added to the end. this is up to the end
+// of a blank line, since nothing continues past one, and before any code block which hasn't been closed yet. the
+// rest of the text starts with a newline.
+pub(crate) fn stable_len(text: &str, options: &Options) -> usize {
+    let (segments, unclosed) = segments(text, options, false);
+    // the text after a code block which hasn't been closed may become part of it
+    let end = unclosed.unwrap_or(text.len());
+    segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Text(span) if span.start < end => text[span.start..span.end.min(end)]
+                .rfind("\n\n")
+                .map(|x| span.start + x + 1),
+            _ => None,
+        })
+        .next_back()
+        .unwrap_or_default()
+}
+
+enum Segment {
+    Text(Range<usize>),
+    // includes the triple backticks
+    Code(Range<usize>),
+    // in provisional mode, a code block which goes to the end of the text. includes the opening backticks.
+    UnclosedCode(Range<usize>),
+}
+
+// also returns where a code block which was never closed starts
+fn segments(text: &str, options: &Options, provisional: bool) -> (Vec<Segment>, Option<usize>) {
+    if options.code_blocks {
+        split_code_blocks(text, options.escapes, provisional)
+    } else {
+        (vec![Segment::Text(0..text.len())], None)
+    }
+}
+
+fn split_code_blocks(
+    text: &str,
+    escapes: bool,
+    provisional: bool,
+) -> (Vec<Segment>, Option<usize>) {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut search_start = 0;
+    let mut unclosed = None;
+    while let Some(open) = text[search_start..].find("```").map(|x| x + search_start) {
+        if escapes && is_escaped(text, open) {
+            // \``` is a backtick followed by 2 more. the next one could still start a code block.
+            search_start = open + 1;
+            continue;
+        }
+
+        // backslashes are just text inside of code, so the closing backticks can't be escaped.
+        let code_start = open + 3;
+        let close = match text[code_start..].find("```") {
+            Some(x) => x + code_start,
+            None if provisional && !text[code_start..].trim().is_empty() => {
+                if open > text_start {
+                    segments.push(Segment::Text(text_start..open));
+                }
+                segments.push(Segment::UnclosedCode(open..text.len()));
+                return (segments, Some(open));
+            }
+            None => {
+                unclosed = Some(open);
+                break;
+            }
+        };
+
+        if text[code_start..close].trim().is_empty() {
+            // empty code blocks are not allowed. the closing backticks may still open the next block.
+            search_start = close;
+            continue;
+        }
+
+        if open > text_start {
+            segments.push(Segment::Text(text_start..open));
+        }
+        segments.push(Segment::Code(open..close + 3));
+        text_start = close + 3;
+        search_start = text_start;
+    }
+
+    if text_start < text.len() {
+        segments.push(Segment::Text(text_start..text.len()));
+    }
+    (segments, unclosed)
+}
+
+// true if there is an odd number of backslashes before `pos`
+fn is_escaped(text: &str, pos: usize) -> bool {
+    text[..pos]
+        .bytes()
+        .rev()
+        .take_while(|x| *x == b'\\')
+        .count()
+        % 2
+        == 1
+}
+
+// the ranges are the trimmed text of the line, relative to the start of the line
+enum Line {
+    Heading(u8, Range<usize>),
+    BlockQuote(Range<usize>),
+    ListItem(ListMarker),
+    Plain(Range<usize>),
+}
+
+struct ListMarker {
+    // tabs count as 4 spaces
+    indent: usize,
+    // the number of an ordered list item
+    number: Option<u64>,
+    // from the marker to the end of the line
+    item: Range<usize>,
+    content: Range<usize>,
+}
+
+impl Line {
+    fn new(line: &str, options: &Options) -> Self {
+        // a heading needs a space after the octothorpes and a title.
+        let level = line.bytes().take_while(|x| *x == b'#').count();
+        if options.heading(level) {
+            let title = &line[level..];
+            if title.starts_with(char::is_whitespace) && !title.trim().is_empty() {
+                return Line::Heading(level as u8, trim_range(line, level..line.len()));
+            }
+        }
+
+        if options.block_quotes && line.starts_with("> ") {
+            return Line::BlockQuote(trim_range(line, 2..line.len()));
+        }
+
+        match ListMarker::new(line, options) {
+            Some(marker) => Line::ListItem(marker),
+            None => Line::Plain(0..line.len()),
+        }
+    }
+}
+
+impl ListMarker {
+    // `- x`, `* x`, `+ x`, `1. x` or `1) x`. the marker needs a space after it and some text.
+    fn new(line: &str, options: &Options) -> Option<Self> {
+        let rest = line.trim_start_matches([' ', '\t']);
+        let marker_start = line.len() - rest.len();
+        let (number, marker_len) = match rest.bytes().next()? {
+            b'-' | b'*' | b'+' if options.unordered_lists => (None, 1),
+            b'0'..=b'9' if options.ordered_lists => {
+                // more than 9 digits isn't a list
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                if digits > 9 || !matches!(rest.as_bytes().get(digits), Some(b'.' | b')')) {
+                    return None;
+                }
+                (rest[..digits].parse().ok(), digits + 1)
+            }
+            _ => return None,
+        };
+
+        let content_start = marker_start + marker_len;
+        if !line[content_start..].starts_with([' ', '\t']) {
+            return None;
+        }
+        let content = trim_range(line, content_start..line.len());
+        if content.is_empty() {
+            return None;
+        }
+
+        let indent = line[..marker_start]
+            .bytes()
+            .map(|x| if x == b'\t' { 4 } else { 1 })
+            .sum();
+        Some(Self {
+            indent,
+            number,
+            item: marker_start..line.len(),
+            content,
+        })
+    }
+}
+
+// `#heading` and `>quote` were probably meant to be a heading and a block quote
+fn missing_space(line: &str, options: &Options) -> Option<DiagnosticKind> {
+    let level = line.bytes().take_while(|x| *x == b'#').count();
+    let rest = &line[level..];
+    let is_word = |text: &str| text.starts_with(|c: char| !c.is_whitespace());
+    if options.heading(level) && is_word(rest) {
+        return Some(DiagnosticKind::HeadingMissingSpace);
+    }
+    match line.strip_prefix('>') {
+        Some(rest) if options.block_quotes && is_word(rest) => {
+            Some(DiagnosticKind::BlockQuoteMissingSpace)
+        }
+        _ => None,
+    }
+}
+
+fn trim_range(text: &str, range: Range<usize>) -> Range<usize> {
+    let slice = &text[range.clone()];
+    let start = range.start + slice.len() - slice.trim_start().len();
+    let end = range.start + slice.trim_end().len();
+    start..end.max(start)
+}
+
+// a block which continues on the next line
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Continued {
+    None,
+    BlockQuote,
+    List,
+}
+
+// a list which may still get more items
+struct OpenList {
+    indent: usize,
+    start: Option<u64>,
+    items: Vec<ListItem>,
+    span: Range<usize>,
+}
+
+impl OpenList {
+    fn into_block(self) -> Block {
+        let kind = BlockKind::List {
+            start: self.start,
+            items: self.items,
+        };
+        Block::new(kind, self.span)
+    }
+}
+
+struct BlockBuilder<'a> {
+    options: &'a Options,
+    blocks: Vec<Block>,
+    paragraph: Vec<Inline>,
+    // a code block can end in the middle of a line. whatever follows it can't be a heading, block quote or list.
+    mid_line: bool,
+    // want something like this:
+    // > line 1
+    // > line 2
+    // to be in a single blockquote tag, and the same for list items. the newline between them is dropped.
+    continued: Continued,
+    // the list being built and its nested lists. the first one is the outermost.
+    lists: Vec<OpenList>,
+    // in provisional mode, the start of the last line
+    provisional_from: Option<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> BlockBuilder<'a> {
+    fn new(options: &'a Options) -> Self {
+        Self {
+            options,
+            blocks: Vec::new(),
+            paragraph: Vec::new(),
+            mid_line: false,
+            continued: Continued::None,
+            lists: Vec::new(),
+            provisional_from: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn inline(&mut self, text: &str, offset: usize) -> Vec<Inline> {
+        let provisional = self
+            .provisional_from
+            .map(|x| offset >= x)
+            .unwrap_or_default();
+        let (inlines, diagnostics) = parse_inline(self.options, text, offset, provisional);
+        self.diagnostics.extend(diagnostics);
+        inlines
+    }
+
+    fn add_text(&mut self, text: &str, text_offset: usize) {
+        let mut line_start = text_offset;
+        // whether the previous line was paragraph text, for soft breaks
+        let mut after_text = false;
+        for (idx, line) in text.split('\n').enumerate() {
+            let line_span = line_start..line_start + line.len();
+            line_start = line_span.end + 1;
+
+            let kind = if idx == 0 && self.mid_line {
+                Line::Plain(0..line.len())
+            } else {
+                Line::new(line, self.options)
+            };
+
+            let continued = match kind {
+                Line::BlockQuote(_) => Continued::BlockQuote,
+                Line::ListItem(_) => Continued::List,
+                _ => Continued::None,
+            };
+            let is_text = matches!(kind, Line::Plain(_)) && !line.trim().is_empty();
+            if idx > 0 && (continued == Continued::None || continued != self.continued) {
+                self.close_lists();
+                let newline = line_span.start - 1;
+                let kind = match self.options.soft_breaks && after_text && is_text {
+                    true => InlineKind::Text(" ".to_string()),
+                    false => InlineKind::LineBreak,
+                };
+                self.paragraph.push(Inline::new(kind, newline..newline + 1));
+            }
+            after_text = is_text;
+
+            match kind {
+                Line::Heading(level, title) => {
+                    self.flush_paragraph();
+                    let content = self.inline(&line[title.clone()], line_span.start + title.start);
+                    self.blocks
+                        .push(Block::new(BlockKind::Heading { level, content }, line_span));
+                }
+                Line::BlockQuote(quote) => {
+                    let quote = self.inline(&line[quote.clone()], line_span.start + quote.start);
+                    match self.blocks.last_mut() {
+                        Some(Block {
+                            kind: BlockKind::BlockQuote(lines),
+                            span,
+                        }) if self.continued == Continued::BlockQuote => {
+                            lines.push(quote);
+                            span.end = line_span.end;
+                        }
+                        _ => {
+                            self.flush_paragraph();
+                            self.blocks
+                                .push(Block::new(BlockKind::BlockQuote(vec![quote]), line_span));
+                        }
+                    }
+                }
+                Line::ListItem(marker) => {
+                    self.flush_paragraph();
+                    let content = self.inline(
+                        &line[marker.content.clone()],
+                        line_span.start + marker.content.start,
+                    );
+                    let item = ListItem {
+                        content,
+                        children: Vec::new(),
+                        span: offset(marker.item.clone(), line_span.start),
+                    };
+                    self.add_list_item(marker.indent, marker.number, item, line_span.start);
+                }
+                Line::Plain(text) => {
+                    if let Some(kind) =
+                        missing_space(line, self.options).filter(|_| idx > 0 || !self.mid_line)
+                    {
+                        self.diagnostics
+                            .push(Diagnostic::new(kind, line_span.clone()));
+                    }
+                    let inlines = self.inline(&line[text.clone()], line_span.start + text.start);
+                    self.paragraph.extend(inlines);
+                }
+            }
+            self.continued = continued;
+        }
+    }
+
+    // an item that is indented more than the one before it starts a nested list. an item that is indented less
+    // closes the nested lists until it lines up with one.
+    fn add_list_item(
+        &mut self,
+        indent: usize,
+        number: Option<u64>,
+        item: ListItem,
+        line_start: usize,
+    ) {
+        while self.lists.len() > 1
+            && self
+                .lists
+                .last()
+                .map(|x| indent < x.indent)
+                .unwrap_or_default()
+        {
+            self.close_nested_list();
+        }
+
+        // switching between numbers and bullets starts a new list
+        if let Some(list) = self.lists.last() {
+            if indent <= list.indent && list.start.is_some() != number.is_some() {
+                if self.lists.len() > 1 {
+                    self.close_nested_list();
+                } else {
+                    // the lists are separate blocks, so the newline between them is kept like any other
+                    self.close_lists();
+                    let newline = line_start - 1;
+                    self.paragraph
+                        .push(Inline::new(InlineKind::LineBreak, newline..newline + 1));
+                    self.flush_paragraph();
+                }
+            }
+        }
+
+        // past the maximum depth, more indented items join the deepest list
+        let is_full = self.lists.len() >= self.options.depth();
+        match self.lists.last_mut() {
+            Some(list) if indent <= list.indent || is_full => {
+                list.span.end = item.span.end;
+                list.items.push(item);
+            }
+            _ => {
+                let indent = match self.lists.last() {
+                    Some(_) => indent,
+                    // the outermost list always starts at 0 so less indented items are still part of it
+                    None => 0,
+                };
+                self.lists.push(OpenList {
+                    indent,
+                    start: number,
+                    span: item.span.clone(),
+                    items: vec![item],
+                });
+            }
+        }
+    }
+
+    // moves the innermost list into the last item of its parent
+    fn close_nested_list(&mut self) {
+        let Some(list) = self.lists.pop() else {
+            return;
+        };
+        match self.lists.last_mut() {
+            Some(parent) => {
+                parent.span.end = list.span.end;
+                if let Some(item) = parent.items.last_mut() {
+                    item.span.end = list.span.end;
+                    item.children.push(list.into_block());
+                }
+            }
+            None => self.blocks.push(list.into_block()),
+        }
+    }
+
+    fn close_lists(&mut self) {
+        while !self.lists.is_empty() {
+            self.close_nested_list();
+        }
+    }
+
+    // the span includes the backticks
+    fn add_code_block(&mut self, code: &str, span: Range<usize>) {
+        // this is specifically designed to work with prismjs. the first line, or the first word if there is only
+        // one line, is the language.
+        let split = match self.options.code_block_language {
+            true => code.find('\n').or_else(|| code.find(' ')),
+            false => None,
+        };
+        let (language, code) = match split {
+            Some(x) if !code[..x].trim().is_empty() => (Some(code[..x].trim()), &code[x + 1..]),
+            _ => (None, code),
+        };
+
+        self.close_lists();
+        self.flush_paragraph();
+        let kind = BlockKind::CodeBlock {
+            language: language.map(String::from),
+            code: code.trim().to_string(),
+        };
+        self.blocks.push(Block::new(kind, span));
+        self.mid_line = true;
+        self.continued = Continued::None;
+    }
+
+    fn flush_paragraph(&mut self) {
+        if let (Some(first), Some(last)) = (self.paragraph.first(), self.paragraph.last()) {
+            let span = first.span.start..last.span.end;
+            let paragraph = std::mem::take(&mut self.paragraph);
+            self.blocks
+                .push(Block::new(BlockKind::Paragraph(paragraph), span));
+        }
+    }
+
+    fn finish(mut self) -> (Document, Vec<Diagnostic>) {
+        self.close_lists();
+        self.flush_paragraph();
+        let document = Document {
+            blocks: self.blocks,
+        };
+        (document, self.diagnostics)
+    }
+}
+
+fn offset(range: Range<usize>, offset: usize) -> Range<usize> {
+    range.start + offset..range.end + offset
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Delimiter {
+    // the text of the line
+    Line,
+    // a run of stars, which can be italics, bold or both
+    Star,
+    // a run of underscores
+    Underscore,
+    // code
+    Backtick,
+    // ``x` is a backtick followed by code
+    DoubleBacktick,
+    // strikethrough
+    DoubleTilde,
+    // also strikethrough, if `Options::single_tilde` is set
+    Tilde,
+    // spoiler
+    DoublePipe,
+    // link text
+    Bracket,
+}
+
+impl Delimiter {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Delimiter::Line => "",
+            Delimiter::Star => "*",
+            Delimiter::Underscore => "_",
+            Delimiter::Backtick => "`",
+            Delimiter::DoubleBacktick => "``",
+            Delimiter::DoubleTilde => "~~",
+            Delimiter::Tilde => "~",
+            Delimiter::DoublePipe => "||",
+            Delimiter::Bracket => "[",
+        }
+    }
+
+    fn is_code(&self) -> bool {
+        matches!(self, Delimiter::Backtick | Delimiter::DoubleBacktick)
+    }
+}
+
+struct StackEntry {
+    delimiter: Delimiter,
+    // where the marker starts
+    start: usize,
+    // the length of the marker. a run of stars or underscores gets shorter as the end of it is closed.
+    len: usize,
+    // the length of the whole run, and whether it could have closed emphasis instead
+    run: usize,
+    can_close: bool,
+    // the closing marker of an empty pair, which opens again. the pair was already reported, so it isn't reported
+    // as unclosed too.
+    reported: bool,
+    children: Vec<Inline>,
+}
+
+impl StackEntry {
+    fn new(delimiter: Delimiter, start: usize) -> Self {
+        let len = delimiter.as_str().len();
+        Self {
+            delimiter,
+            start,
+            len,
+            run: len,
+            can_close: false,
+            reported: false,
+            children: Vec::new(),
+        }
+    }
+
+    fn marker(&self) -> String {
+        match self.delimiter {
+            Delimiter::Star | Delimiter::Underscore => self.delimiter.as_str().repeat(self.len),
+            _ => self.delimiter.as_str().to_string(),
+        }
+    }
+
+    fn marker_span(&self) -> Range<usize> {
+        self.start..self.start + self.len
+    }
+}
+
+// `offset` is the position of the text in the message
+fn parse_inline(
+    options: &Options,
+    text: &str,
+    offset: usize,
+    provisional: bool,
+) -> (Vec<Inline>, Vec<Diagnostic>) {
+    let mut parser = InlineParser {
+        options,
+        provisional,
+        diagnostics: Vec::new(),
+        stack: vec![StackEntry::new(Delimiter::Line, offset)],
+        empty_end: 0,
+    };
+
+    // a `[` is only a marker if there is a `](` after it
+    let link_end = text.rfind("](").filter(|_| options.links);
+    let destinations = match link_end {
+        Some(_) => link_destinations(text),
+        None => HashMap::new(),
+    };
+    // urls are skipped over once they are found
+    let mut skip_to = 0;
+    let mut prev = ' ';
+
+    let mut chars = text.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let pos = offset + idx;
+        let prev = std::mem::replace(&mut prev, c);
+        if idx < skip_to {
+            continue;
+        }
+        let (top, _) = parser.top();
+        // markdown inside of code is just text
+        if top.is_code() && c != '`' {
+            parser.push_char(c, pos);
+            continue;
+        }
+
+        match c {
+            // a backslash makes the next marker text. it has to be punctuation so \n and paths like C:\dir aren't changed.
+            '\\' if options.escapes => match chars.next_if(|(_, c)| c.is_ascii_punctuation()) {
+                Some((_, c)) => parser.push_str(c.encode_utf8(&mut [0; 4]), pos..pos + 2),
+                None => parser.push_char('\\', pos),
+            },
+            '*' | '_' => {
+                let mut len = 1;
+                while chars.next_if(|(_, x)| *x == c).is_some() {
+                    len += 1;
+                }
+                let delimiter = match c {
+                    '*' => Delimiter::Star,
+                    _ => Delimiter::Underscore,
+                };
+                parser.emphasis(delimiter, pos, len, prev, &text[idx + len..]);
+            }
+            '`' if options.inline_code => parser.backtick(&text[idx + 1..], pos),
+            // a single tilde means nothing, unless it is enabled
+            '~' if options.strikethrough && chars.next_if(|(_, c)| *c == '~').is_some() => {
+                parser.pair(Delimiter::DoubleTilde, pos)
+            }
+            '~' if options.strikethrough && options.single_tilde => {
+                parser.pair(Delimiter::Tilde, pos)
+            }
+            // a single pipe means nothing either
+            '|' if options.spoilers && chars.next_if(|(_, c)| *c == '|').is_some() => {
+                parser.pair(Delimiter::DoublePipe, pos)
+            }
+            '[' if link_end.map(|x| x > idx).unwrap_or_default() => {
+                parser.open(Delimiter::Bracket, pos)
+            }
+            ']' if options.links => match destinations.get(&idx) {
+                // `](` and `)`
+                Some(len) if parser.link(&text[idx + 2..idx + 2 + len], pos) => {
+                    skip_to = idx + len + 3
+                }
+                _ => parser.push_char(c, pos),
+            },
+            // a mention has to start a word, so email addresses aren't mentions
+            '@' if options.mentions && !prev.is_alphanumeric() && !parser.in_link() => {
+                match mentions::mention_len(&text[idx + 1..]) {
+                    Some(len) => {
+                        let name = &text[idx + 1..idx + 1 + len];
+                        let kind = InlineKind::Mention(name.to_string());
+                        parser.push(Inline::new(kind, pos..pos + 1 + len));
+                        skip_to = idx + 1 + len;
+                    }
+                    None => parser.push_char(c, pos),
+                }
+            }
+            ':' | '<' if options.shortcodes || options.emoticons => {
+                match parser.emoji(&text[idx..], pos, prev) {
+                    Some(len) => skip_to = idx + len,
+                    None => parser.push_char(c, pos),
+                }
+            }
+            // a url has to start a word. urls inside of link text are left alone.
+            'h' | 'H' | 'w' | 'W'
+                if options.autolinks && !prev.is_alphanumeric() && !parser.in_link() =>
+            {
+                match links::autolink_len(&text[idx..]) {
+                    Some(len) => {
+                        // a url which isn't allowed is text, rather than being searched again from each `h` in it
+                        let url = &text[idx..idx + len];
+                        if !parser.autolink(url, pos) {
+                            parser.push_str(url, pos..pos + len);
+                        }
+                        skip_to = idx + len;
+                    }
+                    None => parser.push_char(c, pos),
+                }
+            }
+            c => parser.push_char(c, pos),
+        }
+    }
+
+    parser.finish(offset + text.len())
+}
+
+// a stack of the markers which haven't been closed yet. the bottom of the stack is the line itself.
+struct InlineParser<'a> {
+    options: &'a Options,
+    // markers which aren't closed by the end of the line are closed anyway
+    provisional: bool,
+    diagnostics: Vec<Diagnostic>,
+    stack: Vec<StackEntry>,
+    // the end of the last empty pair of `*` or `_`, so its second run isn't reported again
+    empty_end: usize,
+}
+
+impl InlineParser<'_> {
+    // returns the top delimiter and whether anything came after it
+    fn top(&self) -> (Delimiter, bool) {
+        self.stack
+            .last()
+            .map(|x| (x.delimiter, x.children.is_empty()))
+            .expect("stack should not be empty")
+    }
+
+    // pops an empty marker so it can be combined with the next one. returns where the marker started.
+    fn pop_empty(&mut self) -> usize {
+        self.stack
+            .pop()
+            .map(|x| x.start)
+            .expect("stack should not be empty")
+    }
+
+    // a run of `*` or `_`, after the character `before` and followed by `rest`. this follows the commonmark rules, so
+    // a run can only open emphasis at the start of a word and close it at the end of one, and underscores inside of
+    // a word are text. see https://spec.commonmark.org/0.31.2/#emphasis-and-strong-emphasis
+    fn emphasis(&mut self, delimiter: Delimiter, pos: usize, run: usize, before: char, rest: &str) {
+        let after = rest.chars().next().unwrap_or(' ');
+        let (single_enabled, double_enabled) = match delimiter {
+            Delimiter::Star => (self.options.star_emphasis, self.options.star_strong),
+            _ => (
+                self.options.underscore_emphasis,
+                self.options.underscore_strong || self.options.underscore_underline,
+            ),
+        };
+        let marker = delimiter.as_str();
+        // without bold, `**` is just text
+        if !single_enabled && (!double_enabled || run == 1) || !double_enabled && run > 1 {
+            self.push_str(&marker.repeat(run), pos..pos + run);
+            return;
+        }
+
+        let left_flanking = !after.is_whitespace()
+            && (!is_punctuation(after) || before.is_whitespace() || is_punctuation(before));
+        let right_flanking = !before.is_whitespace()
+            && (!is_punctuation(before) || after.is_whitespace() || is_punctuation(after));
+        let (can_open, can_close) = match delimiter {
+            Delimiter::Star => (left_flanking, right_flanking),
+            _ => (
+                left_flanking && (!right_flanking || is_punctuation(before)),
+                right_flanking && (!left_flanking || is_punctuation(after)),
+            ),
+        };
+        if !left_flanking && !right_flanking {
+            self.empty_pair(delimiter, pos, run, rest);
+        }
+
+        // the start of the run closes the nearest opener, two at a time if both have enough
+        let (mut pos, mut len) = (pos, run);
+        while can_close && len > 0 {
+            let Some(index) = self.opener(delimiter, run, can_open) else {
+                break;
+            };
+            let count = match self.stack[index].len >= 2 && len >= 2 && double_enabled {
+                true => 2,
+                false if single_enabled => 1,
+                false => break,
+            };
+
+            // markers in between which weren't closed are text
+            while self.stack.len() > index + 1 {
+                let entry = self.stack.pop().expect("stack should not be empty");
+                self.unclosed(entry);
+            }
+            let mut entry = self.stack.pop().expect("stack should not be empty");
+            entry.len -= count;
+            let span = entry.start + entry.len..pos + count;
+            let children = trim(std::mem::take(&mut entry.children));
+            let kind = match count {
+                2 => self.double_kind(delimiter, children),
+                _ => InlineKind::Emphasis(children),
+            };
+            let node = Inline::new(kind, span);
+            if entry.len > 0 {
+                entry.children.push(node);
+                self.stack.push(entry);
+            } else {
+                self.push(node);
+            }
+            pos += count;
+            len -= count;
+        }
+
+        if len > 0 && can_open && !self.too_deep() {
+            self.stack.push(StackEntry {
+                len,
+                run,
+                can_close,
+                ..StackEntry::new(delimiter, pos)
+            });
+        } else if len > 0 {
+            self.push_str(&marker.repeat(len), pos..pos + len);
+        }
+    }
+
+    // a run with whitespace on both sides is text, but it looks like an empty pair if it is `****`, or if the same
+    // run comes after nothing but whitespace, like `** **`
+    fn empty_pair(&mut self, delimiter: Delimiter, pos: usize, run: usize, rest: &str) {
+        if pos < self.empty_end {
+            return;
+        }
+        let marker = delimiter.as_str().repeat(run);
+        let trimmed = rest.trim_start();
+        let closing = trimmed
+            .strip_prefix(marker.as_str())
+            .filter(|x| x.chars().next().map(char::is_whitespace).unwrap_or(true));
+        let end = match closing {
+            _ if run >= 4 => pos + run,
+            Some(_) => pos + run + rest.len() - trimmed.len() + run,
+            None => return,
+        };
+        self.diagnostics
+            .push(Diagnostic::new(DiagnosticKind::Empty, pos..end));
+        self.empty_end = end;
+    }
+
+    // `**x**`, or `__x__` which may be underlined instead
+    fn double_kind(&self, delimiter: Delimiter, children: Vec<Inline>) -> InlineKind {
+        match delimiter {
+            Delimiter::Underscore if self.options.underscore_underline => {
+                InlineKind::Underline(children)
+            }
+            _ => InlineKind::Strong(children),
+        }
+    }
+
+    // the index of the nearest run which can be closed by a run of `len`. emphasis can't cross into link text.
+    fn opener(&self, delimiter: Delimiter, len: usize, can_open: bool) -> Option<usize> {
+        for (index, entry) in self.stack.iter().enumerate().rev() {
+            if entry.delimiter == Delimiter::Bracket {
+                return None;
+            }
+            if entry.delimiter != delimiter {
+                continue;
+            }
+            // the rule of 3, so `*a**b*` is emphasis around `a**b`
+            let is_multiple = (entry.run + len).is_multiple_of(3)
+                && !(entry.run.is_multiple_of(3) && len.is_multiple_of(3));
+            if (can_open || entry.can_close) && is_multiple {
+                continue;
+            }
+            return Some(index);
+        }
+        None
+    }
+
+    // a backtick is only a marker if there is another backtick later in the line.
+    fn backtick(&mut self, rest: &str, pos: usize) {
+        let has_closing = self.provisional || rest.contains('`');
+        match self.top() {
+            (Delimiter::Backtick, true) => {
+                let start = self.pop_empty();
+                if has_closing {
+                    self.open(Delimiter::DoubleBacktick, start);
+                } else {
+                    self.push_str("``", start..pos + 1);
+                }
+            }
+            (Delimiter::Backtick, false) => {
+                if !self.close(pos + 1) {
+                    if has_closing {
+                        self.open(Delimiter::Backtick, pos);
+                    } else {
+                        self.push_char('`', pos);
+                    }
+                }
+            }
+            (Delimiter::DoubleBacktick, true) => {
+                // a code block would have been found before parsing the line
+                let start = self.pop_empty();
+                self.push_str("```", start..pos + 1);
+            }
+            (Delimiter::DoubleBacktick, false) => {
+                // the pattern looks like this: ``[\w+]`. make a code segment.
+                let mut entry = self.stack.pop().expect("stack should not be empty");
+                self.push_char('`', entry.start);
+                entry.delimiter = Delimiter::Backtick;
+                entry.start += 1;
+                entry.len = 1;
+                self.stack.push(entry);
+                if !self.close(pos + 1) {
+                    self.push_char('`', pos);
+                }
+            }
+            _ if has_closing => self.open(Delimiter::Backtick, pos),
+            _ => self.push_char('`', pos),
+        }
+    }
+
+    // `~~`, `~` and `||`, where the same marker opens and closes
+    fn pair(&mut self, delimiter: Delimiter, pos: usize) {
+        let end = pos + delimiter.as_str().len();
+        if self.top().0 != delimiter {
+            self.open(delimiter, pos);
+        } else if !self.close(end) {
+            self.open(delimiter, pos);
+            if let Some(entry) = self.stack.last_mut().filter(|x| x.start == pos) {
+                entry.reported = true;
+            }
+        }
+    }
+
+    fn open(&mut self, delimiter: Delimiter, start: usize) {
+        let entry = StackEntry::new(delimiter, start);
+        match self.too_deep() {
+            true => self.put_back(entry),
+            false => self.stack.push(entry),
+        }
+    }
+
+    // true if no more markers can be opened. the line itself is the bottom of the stack.
+    fn too_deep(&self) -> bool {
+        self.stack.len() > self.options.depth()
+    }
+
+    // converts the top of the stack into a node which ends at `end`. empty tags or just whitespace are not
+    // allowed - if the tag is empty, the text is put back and false is returned.
+    fn close(&mut self, end: usize) -> bool {
+        let entry = self.stack.pop().expect("stack should not be empty");
+        if self.stack.is_empty() {
+            // should never happen
+            self.stack.push(entry);
+            return false;
+        }
+
+        if is_blank(&entry.children) {
+            let span = entry.start..end;
+            self.diagnostics
+                .push(Diagnostic::new(DiagnosticKind::Empty, span));
+            self.put_back(entry);
+            return false;
+        }
+
+        let span = entry.start..end;
+        let children = trim(entry.children);
+        let kind = match entry.delimiter {
+            // only in provisional mode
+            Delimiter::Star | Delimiter::Underscore if entry.len >= 2 => {
+                self.double_kind(entry.delimiter, children)
+            }
+            Delimiter::Star | Delimiter::Underscore => InlineKind::Emphasis(children),
+            Delimiter::Tilde | Delimiter::DoubleTilde => InlineKind::Strikethrough(children),
+            Delimiter::DoublePipe => InlineKind::Spoiler(children),
+            Delimiter::Backtick
+            | Delimiter::DoubleBacktick
+            | Delimiter::Line
+            | Delimiter::Bracket => {
+                let code = children
+                    .into_iter()
+                    .fold(String::new(), |acc, x| match x.kind {
+                        InlineKind::Text(text) => acc + &text,
+                        _ => acc,
+                    });
+                InlineKind::Code(code)
+            }
+        };
+        self.push(Inline::new(kind, span));
+        true
+    }
+
+    // a `]` followed by `](url)`. returns false if it isn't a link, and the `[` is text from then on, like in
+    // commonmark. otherwise a line full of `](` could check the same `[` again and again.
+    fn link(&mut self, url: &str, pos: usize) -> bool {
+        let Some(bracket) = self
+            .stack
+            .iter()
+            .rposition(|x| x.delimiter == Delimiter::Bracket)
+        else {
+            return false;
+        };
+
+        // links can't be nested
+        let is_nested = self.stack[bracket..]
+            .iter()
+            .flat_map(|x| &x.children)
+            .any(|x| matches!(x.kind, InlineKind::Link { .. }));
+        let Some(href) = links::href(url, self.options).filter(|_| !is_nested) else {
+            let above = self.stack.split_off(bracket + 1);
+            let entry = self.stack.pop().expect("stack should not be empty");
+            self.put_back(entry);
+            self.stack.extend(above);
+            return false;
+        };
+
+        // markers inside of the link text which weren't closed are just text
+        while self.stack.len() > bracket + 1 {
+            let entry = self.stack.pop().expect("stack should not be empty");
+            self.unclosed(entry);
+        }
+        let entry = self.stack.pop().expect("stack should not be empty");
+        if is_blank(&entry.children) {
+            self.put_back(entry);
+            return false;
+        }
+
+        // `](` and `)`
+        let len = url.len() + 3;
+        let kind = InlineKind::Link {
+            url: href,
+            content: trim(entry.children),
+        };
+        self.push(Inline::new(kind, entry.start..pos + len));
+        true
+    }
+
+    // returns false if the url isn't allowed
+    fn autolink(&mut self, url: &str, pos: usize) -> bool {
+        let Some(href) = links::href(url, self.options) else {
+            return false;
+        };
+        let span = pos..pos + url.len();
+        let kind = InlineKind::Link {
+            url: href,
+            content: vec![Inline::new(InlineKind::Text(url.to_string()), span.clone())],
+        };
+        self.push(Inline::new(kind, span));
+        true
+    }
+
+    // `:name:` or an emoticon. both have to start a word, so times like 10:30:00 are left alone. returns the length.
+    fn emoji(&mut self, rest: &str, pos: usize, prev: char) -> Option<usize> {
+        if prev.is_alphanumeric() || self.in_link() {
+            return None;
+        }
+        let shortcode = rest
+            .strip_prefix(':')
+            .filter(|_| self.options.shortcodes)
+            .and_then(emoji::shortcode_len)
+            .map(|len| (&rest[1..len + 1], len + 2));
+        let emoticon = || emoji::emoticon(rest).filter(|_| self.options.emoticons);
+        let (name, len) = shortcode.or_else(emoticon)?;
+
+        let kind = InlineKind::Emoji(name.to_string());
+        self.push(Inline::new(kind, pos..pos + len));
+        Some(len)
+    }
+
+    fn in_link(&self) -> bool {
+        self.stack.iter().any(|x| x.delimiter == Delimiter::Bracket)
+    }
+
+    // the marker was never closed, so it is text. a `[` without a link isn't a mistake.
+    fn unclosed(&mut self, entry: StackEntry) {
+        if entry.delimiter != Delimiter::Bracket && !entry.reported {
+            let span = entry.marker_span();
+            self.diagnostics
+                .push(Diagnostic::new(DiagnosticKind::Unclosed, span));
+        }
+        self.put_back(entry);
+    }
+
+    // the marker wasn't used, so it is text
+    fn put_back(&mut self, entry: StackEntry) {
+        self.push_str(&entry.marker(), entry.marker_span());
+        entry.children.into_iter().for_each(|x| self.push(x));
+    }
+
+    fn push(&mut self, node: Inline) {
+        match node.kind {
+            InlineKind::Text(text) => self.push_str(&text, node.span),
+            _ => {
+                if let Some(entry) = self.stack.last_mut() {
+                    entry.children.push(node);
+                }
+            }
+        }
+    }
+
+    // text is merged with the text before it
+    fn push_str(&mut self, text: &str, span: Range<usize>) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(entry) = self.stack.last_mut() {
+            match entry.children.last_mut() {
+                Some(Inline {
+                    kind: InlineKind::Text(x),
+                    span: prev,
+                }) => {
+                    x.push_str(text);
+                    prev.end = span.end;
+                }
+                _ => entry
+                    .children
+                    .push(Inline::new(InlineKind::Text(text.to_string()), span)),
+            }
+        }
+    }
+
+    fn push_char(&mut self, c: char, pos: usize) {
+        self.push_str(c.encode_utf8(&mut [0; 4]), pos..pos + c.len_utf8());
+    }
+
+    // anything left on the stack was never closed and is just text
+    // `end` is the end of the line. in provisional mode, the markers are closed there.
+    fn finish(mut self, end: usize) -> (Vec<Inline>, Vec<Diagnostic>) {
+        while self.stack.len() > 1 {
+            let (top, _) = self.top();
+            if !self.provisional || top == Delimiter::Bracket {
+                let entry = self.stack.pop().expect("stack should not be empty");
+                self.unclosed(entry);
+            } else {
+                self.close(end);
+            }
+        }
+        let inlines = self.stack.pop().map(|x| x.children).unwrap_or_default();
+        (inlines, self.diagnostics)
+    }
+}
+
+// the length of the url in each `](url)` in the line, by the index of the `]`. the url can't have spaces, but can
+// have balanced parentheses. they are all found in one pass from the end of the line, so a line full of `](` is
+// still linear.
+fn link_destinations(text: &str) -> HashMap<usize, usize> {
+    // how many parentheses are open before each character
+    let mut depth = 0i64;
+    let chars: Vec<(usize, char, i64)> = text
+        .char_indices()
+        .map(|(idx, c)| {
+            let before = depth;
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (idx, c, before)
+        })
+        .collect();
+
+    let mut urls = HashMap::new();
+    // the nearest `)` after the current character for each depth, and the nearest space
+    let mut closing: HashMap<i64, usize> = HashMap::new();
+    let mut space = text.len();
+    for (i, &(idx, c, depth)) in chars.iter().enumerate().rev() {
+        match c {
+            ')' => {
+                closing.insert(depth, idx);
+            }
+            // the url ends at the first `)` which closes this one
+            '(' if i > 0 && chars[i - 1].1 == ']' => {
+                if let Some(&end) = closing.get(&(depth + 1)).filter(|x| **x < space) {
+                    urls.insert(idx - 1, end - idx - 1);
+                }
+            }
+            c if c.is_whitespace() || c.is_control() => space = idx,
+            _ => {}
+        }
+    }
+    urls
+}
+
+// commonmark counts symbols as punctuation too, which includes emoji. combining marks and joiners are part of the
+// character before them, so they aren't.
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || !(c.is_ascii() || c.is_alphanumeric() || c.is_whitespace() || is_extend(c))
+}
+
+// true if `c` joins onto the character before it, like the accent in a decomposed `é` or a zero width joiner
+fn is_extend(c: char) -> bool {
+    let mut buffer = [b'a'; 5];
+    let len = c.encode_utf8(&mut buffer[1..]).len() + 1;
+    std::str::from_utf8(&buffer[..len])
+        .map(|x| x.graphemes(true).count() == 1)
+        .unwrap_or_default()
+}
+
+// true if a tag only has whitespace in it
+fn is_blank(children: &[Inline]) -> bool {
+    children.iter().all(|x| match &x.kind {
+        InlineKind::Text(text) => text.trim().is_empty(),
+        _ => false,
+    })
+}
+
+// removes leading and trailing whitespace from the contents of a tag
+fn trim(mut children: Vec<Inline>) -> Vec<Inline> {
+    if let Some(Inline {
+        kind: InlineKind::Text(text),
+        span,
+    }) = children.first_mut()
+    {
+        let trimmed = text.trim_start();
+        span.start += text.len() - trimmed.len();
+        *text = trimmed.to_string();
+    }
+    if let Some(Inline {
+        kind: InlineKind::Text(text),
+        span,
+    }) = children.last_mut()
+    {
+        let len = text.trim_end().len();
+        span.end -= text.len() - len;
+        text.truncate(len);
+    }
+    children.retain(|x| !matches!(&x.kind, InlineKind::Text(text) if text.is_empty()));
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, parse_with_diagnostics};
+
+    fn text(text: &str, span: Range<usize>) -> Inline {
+        Inline::new(InlineKind::Text(text.to_string()), span)
+    }
+
+    fn line_break(pos: usize) -> Inline {
+        Inline::new(InlineKind::LineBreak, pos..pos + 1)
+    }
+
+    #[test]
+    fn test_paragraph() {
+        let document = parse("hello *world*\n**bye**");
+        let expected = vec![Block::new(
+            BlockKind::Paragraph(vec![
+                text("hello ", 0..6),
+                Inline::new(InlineKind::Emphasis(vec![text("world", 7..12)]), 6..13),
+                line_break(13),
+                Inline::new(InlineKind::Strong(vec![text("bye", 16..19)]), 14..21),
+            ]),
+            0..21,
+        )];
+        assert_eq!(document.blocks, expected);
+    }
+
+    #[test]
+    fn test_nested() {
+        let document = parse("__a *b ~~c~~*__ `d`");
+        let strikethrough = Inline::new(InlineKind::Strikethrough(vec![text("c", 9..10)]), 7..12);
+        let emphasis = Inline::new(
+            InlineKind::Emphasis(vec![text("b ", 5..7), strikethrough]),
+            4..13,
+        );
+        let expected = vec![Block::new(
+            BlockKind::Paragraph(vec![
+                Inline::new(InlineKind::Strong(vec![text("a ", 2..4), emphasis]), 0..15),
+                text(" ", 15..16),
+                Inline::new(InlineKind::Code("d".into()), 16..19),
+            ]),
+            0..19,
+        )];
+        assert_eq!(document.blocks, expected);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let document = parse("# title\n> q1\n> q2\ntext ```rust\nlet a = 0;```");
+        let expected = vec![
+            Block::new(
+                BlockKind::Heading {
+                    level: 1,
+                    content: vec![text("title", 2..7)],
+                },
+                0..7,
+            ),
+            Block::new(BlockKind::Paragraph(vec![line_break(7)]), 7..8),
+            Block::new(
+                BlockKind::BlockQuote(vec![vec![text("q1", 10..12)], vec![text("q2", 15..17)]]),
+                8..17,
+            ),
+            Block::new(
+                BlockKind::Paragraph(vec![line_break(17), text("text ", 18..23)]),
+                17..23,
+            ),
+            Block::new(
+                BlockKind::CodeBlock {
+                    language: Some("rust".into()),
+                    code: "let a = 0;".into(),
+                },
+                23..44,
+            ),
+        ];
+        assert_eq!(document.blocks, expected);
+    }
+
+    #[test]
+    fn test_code_is_text() {
+        let document = parse("`*a* _b_`");
+        let expected = vec![Block::new(
+            BlockKind::Paragraph(vec![Inline::new(InlineKind::Code("*a* _b_".into()), 0..9)]),
+            0..9,
+        )];
+        assert_eq!(document.blocks, expected);
+    }
+
+    #[test]
+    fn test_unclosed() {
+        let document = parse("**a *b");
+        let expected = vec![Block::new(
+            BlockKind::Paragraph(vec![text("**a *b", 0..6)]),
+            0..6,
+        )];
+        assert_eq!(document.blocks, expected);
+    }
+
+    #[test]
+    fn test_spans_match_source() {
+        let test_str = "x ``h`ello ~~ ~~ *é* `` `";
+        let document = parse(test_str);
+        let BlockKind::Paragraph(inlines) = &document.blocks[0].kind else {
+            panic!("expected a paragraph");
+        };
+        let spans: Vec<&str> = inlines.iter().map(|x| &test_str[x.span.clone()]).collect();
+        assert_eq!(spans, vec!["x `", "`h`", "ello ~~ ~~ ", "*é*", " `` `"]);
+    }
+
+    #[test]
+    fn test_trimmed_spans() {
+        let test_str = "#   spaced title  \n>  quote ";
+        let document = parse(test_str);
+        let BlockKind::Heading { content, .. } = &document.blocks[0].kind else {
+            panic!("expected a heading");
+        };
+        assert_eq!(&test_str[content[0].span.clone()], "spaced title");
+        let BlockKind::BlockQuote(lines) = &document.blocks[2].kind else {
+            panic!("expected a block quote");
+        };
+        assert_eq!(&test_str[lines[0][0].span.clone()], "quote");
+        assert_eq!(&test_str[document.blocks[2].span.clone()], ">  quote ");
+    }
+
+    #[test]
+    fn test_escape_span() {
+        let document = parse(r"\*a\` b");
+        let expected = vec![Block::new(
+            BlockKind::Paragraph(vec![text("*a` b", 0..7)]),
+            0..7,
+        )];
+        assert_eq!(document.blocks, expected);
+    }
+
+    #[test]
+    fn test_list_spans() {
+        let document = parse("- a\n  1. b\n- c");
+        let nested = Block::new(
+            BlockKind::List {
+                start: Some(1),
+                items: vec![ListItem {
+                    content: vec![text("b", 9..10)],
+                    children: Vec::new(),
+                    span: 6..10,
+                }],
+            },
+            6..10,
+        );
+        let expected = vec![Block::new(
+            BlockKind::List {
+                start: None,
+                items: vec![
+                    ListItem {
+                        content: vec![text("a", 2..3)],
+                        children: vec![nested],
+                        span: 0..10,
+                    },
+                    ListItem {
+                        content: vec![text("c", 13..14)],
+                        children: Vec::new(),
+                        span: 11..14,
+                    },
+                ],
+            },
+            0..14,
+        )];
+        assert_eq!(document.blocks, expected);
+    }
+
+    #[test]
+    fn test_link_destinations() {
+        let urls = link_destinations("[a](b) [c](d(e)) [f](g h) ](i)(");
+        let expected = HashMap::from([(2, 1), (9, 4), (26, 1)]);
+        assert_eq!(urls, expected);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let test_str =
+            "**bold *ok\n~~ ~~ x || || y\na ** ** __ __ b **** c\n#heading\n>quote\n> fine ```rust\nlet a";
+        let (_, diagnostics) = parse_with_diagnostics(test_str, &Options::default());
+        let found: Vec<(DiagnosticKind, &str)> = diagnostics
+            .iter()
+            .map(|x| (x.kind, &test_str[x.span.clone()]))
+            .collect();
+        let expected = vec![
+            (DiagnosticKind::Unclosed, "**"),
+            (DiagnosticKind::Unclosed, "*"),
+            (DiagnosticKind::Empty, "~~ ~~"),
+            (DiagnosticKind::Empty, "|| ||"),
+            (DiagnosticKind::Empty, "** **"),
+            (DiagnosticKind::Empty, "__ __"),
+            (DiagnosticKind::Empty, "****"),
+            (DiagnosticKind::HeadingMissingSpace, "#heading"),
+            (DiagnosticKind::BlockQuoteMissingSpace, ">quote"),
+            (DiagnosticKind::Unclosed, "```"),
+        ];
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_no_diagnostics() {
+        let test_str = "# a\n> b\n*c* [d] `e` ```f```#g";
+        let (_, diagnostics) = parse_with_diagnostics(test_str, &Options::default());
+        assert!(diagnostics.is_empty());
+    }
+}
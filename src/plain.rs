@@ -0,0 +1,178 @@
+// renders the text without any markup, for things like notifications
+use crate::{ast::Document, render::Renderer};
+
+impl Document {
+    /// renders the document the same way as `text_to_plain`
+    pub fn to_plain(&self) -> String {
+        let mut renderer = PlainRenderer::new();
+        self.render(&mut renderer);
+        renderer.finish()
+    }
+}
+
+// what spoilers are replaced with by default
+const SPOILER_MASK: &str = "[spoiler]";
+
+/// renders a `Document` as plain text. markers are removed, code is kept as is, and list items keep a `- ` or
+/// `1. ` in front of them. spoilers are replaced with `[spoiler]`.
+pub struct PlainRenderer {
+    text: String,
+    // replaces the contents of spoilers, or None to show them
+    spoiler_mask: Option<String>,
+    // how many spoilers the current text is in
+    spoilers: usize,
+    // block quote lines and list items are separated by newlines
+    first_line: bool,
+    // the number of the next item in each open list, or None for bulleted lists
+    lists: Vec<Option<u64>>,
+}
+
+impl Default for PlainRenderer {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            spoiler_mask: Some(SPOILER_MASK.into()),
+            spoilers: 0,
+            first_line: false,
+            lists: Vec::new(),
+        }
+    }
+}
+
+impl PlainRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// replaces each spoiler with `mask` instead of `[spoiler]`
+    pub fn with_spoiler_mask(mut self, mask: impl Into<String>) -> Self {
+        self.spoiler_mask = Some(mask.into());
+        self
+    }
+
+    /// shows the text of spoilers instead of masking them, for places where the user already chose to see them
+    pub fn show_spoilers(mut self, show: bool) -> Self {
+        if show {
+            self.spoiler_mask = None;
+        } else if self.spoiler_mask.is_none() {
+            self.spoiler_mask = Some(SPOILER_MASK.into());
+        }
+        self
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+
+    fn push(&mut self, text: &str) {
+        if self.spoilers == 0 || self.spoiler_mask.is_none() {
+            self.text += text;
+        }
+    }
+}
+
+impl Renderer for PlainRenderer {
+    fn text(&mut self, text: &str) {
+        self.push(text);
+    }
+
+    fn line_break(&mut self) {
+        self.push("\n");
+    }
+
+    fn code(&mut self, code: &str) {
+        self.push(code);
+    }
+
+    fn code_block(&mut self, _language: Option<&str>, code: &str) {
+        self.push(code);
+    }
+
+    fn start_block_quote(&mut self) {
+        self.first_line = true;
+    }
+
+    fn start_block_quote_line(&mut self) {
+        if !std::mem::take(&mut self.first_line) {
+            self.push("\n");
+        }
+    }
+
+    fn start_list(&mut self, start: Option<u64>) {
+        if self.lists.is_empty() {
+            self.first_line = true;
+        }
+        self.lists.push(start);
+    }
+
+    fn end_list(&mut self, _numbered: bool) {
+        self.lists.pop();
+    }
+
+    fn start_list_item(&mut self) {
+        if !std::mem::take(&mut self.first_line) {
+            self.push("\n");
+        }
+        let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+        let marker = match self.lists.last_mut() {
+            Some(Some(number)) => {
+                *number += 1;
+                format!("{}. ", *number - 1)
+            }
+            _ => "- ".to_string(),
+        };
+        self.push(&indent);
+        self.push(&marker);
+    }
+
+    fn start_spoiler(&mut self) {
+        if self.spoilers == 0 {
+            if let Some(mask) = &self.spoiler_mask {
+                self.text += mask;
+            }
+        }
+        self.spoilers += 1;
+    }
+
+    fn end_spoiler(&mut self) {
+        self.spoilers -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, text_to_plain};
+
+    #[test]
+    fn test_plain() {
+        let test_str = "# title\n**bold** _a ~~b~~_ `*code*` ||secret|| [link](https://a.com) :smile: @bob\n> q1\n> q2\n```rust\nlet a = 0;```";
+        let expected = "title\nbold a b *code* [spoiler] link 😄 @bob\nq1\nq2\nlet a = 0;";
+        assert_eq!(text_to_plain(test_str), expected);
+
+        let mut renderer = PlainRenderer::new().show_spoilers(true);
+        parse(test_str).render(&mut renderer);
+        assert_eq!(renderer.finish(), expected.replace("[spoiler]", "secret"));
+    }
+
+    #[test]
+    fn test_plain_lists() {
+        let test_str = "- a\n  3. b\n  4. c\n- d\ntext";
+        let expected = "- a\n  3. b\n  4. c\n- d\ntext";
+        assert_eq!(text_to_plain(test_str), expected);
+    }
+
+    #[test]
+    fn test_plain_switching_lists() {
+        assert_eq!(text_to_plain("- a\n1. b"), "- a\n1. b");
+        assert_eq!(text_to_plain("- a\n  - b\n1. c"), "- a\n  - b\n1. c");
+        assert_eq!(text_to_plain("1. a\n- b\n2. c"), "1. a\n- b\n2. c");
+    }
+
+    #[test]
+    fn test_masked_spoilers() {
+        let mut renderer = PlainRenderer::new().with_spoiler_mask("███");
+        parse("the ||*butler* did it|| with ||a `rope`||!").render(&mut renderer);
+        assert_eq!(renderer.finish(), "the ███ with ███!");
+    }
+}
@@ -0,0 +1,267 @@
+// walks a Document and calls a Renderer for each node. see html.rs for the default renderer.
+use std::ops::Range;
+
+use crate::{
+    ast::{Block, BlockKind, Document, Inline, InlineKind},
+    emoji,
+};
+
+/// receives the nodes of a `Document` in order. container nodes get a start and end callback, with their children
+/// rendered in between. the container callbacks default to doing nothing, so a renderer which only cares about
+/// the text only needs to implement the leaf nodes.
+pub trait Renderer {
+    fn text(&mut self, text: &str);
+    fn line_break(&mut self);
+    /// `` `x` ``
+    fn code(&mut self, code: &str);
+    /// text between triple backticks
+    fn code_block(&mut self, language: Option<&str>, code: &str);
+    /// `@name`. `name` doesn't include the `@`. rendered as text by default.
+    fn mention(&mut self, name: &str) {
+        self.text(&format!("@{name}"));
+    }
+    /// `:name:`. rendered as the unicode emoji by default, or as text if there isn't one.
+    fn emoji(&mut self, name: &str) {
+        match emoji::unicode_emoji(name) {
+            Some(emoji) => self.text(emoji),
+            None => self.text(&format!(":{name}:")),
+        }
+    }
+
+    /// called before the other callbacks with the byte range of the markdown they render. the start callback of a
+    /// container gets the opening marker, and the end callback gets the closing one.
+    fn source(&mut self, _span: Range<usize>) {}
+
+    fn start_paragraph(&mut self) {}
+    fn end_paragraph(&mut self) {}
+    fn start_heading(&mut self, _level: u8) {}
+    fn end_heading(&mut self, _level: u8) {}
+    fn start_block_quote(&mut self) {}
+    fn end_block_quote(&mut self) {}
+    /// called for each line of a block quote
+    fn start_block_quote_line(&mut self) {}
+    fn end_block_quote_line(&mut self) {}
+    /// `start` is the first number of a numbered list, or `None` for a bulleted list
+    fn start_list(&mut self, _start: Option<u64>) {}
+    fn end_list(&mut self, _numbered: bool) {}
+    /// nested lists are rendered after the content of the item, before `end_list_item`
+    fn start_list_item(&mut self) {}
+    fn end_list_item(&mut self) {}
+    fn start_emphasis(&mut self) {}
+    fn end_emphasis(&mut self) {}
+    fn start_strong(&mut self) {}
+    fn end_strong(&mut self) {}
+    fn start_underline(&mut self) {}
+    fn end_underline(&mut self) {}
+    fn start_strikethrough(&mut self) {}
+    fn end_strikethrough(&mut self) {}
+    fn start_spoiler(&mut self) {}
+    fn end_spoiler(&mut self) {}
+    /// `url` has already been checked against `Options::link_schemes`
+    fn start_link(&mut self, _url: &str) {}
+    fn end_link(&mut self) {}
+}
+
+impl Document {
+    /// passes every node to the renderer, in order
+    pub fn render<R: Renderer + ?Sized>(&self, renderer: &mut R) {
+        for block in &self.blocks {
+            render_block(block, renderer);
+        }
+    }
+}
+
+fn render_block<R: Renderer + ?Sized>(block: &Block, renderer: &mut R) {
+    let span = &block.span;
+    match &block.kind {
+        BlockKind::Paragraph(content) => container(
+            renderer,
+            span,
+            content,
+            R::start_paragraph,
+            R::end_paragraph,
+        ),
+        BlockKind::Heading { level, content } => container(
+            renderer,
+            span,
+            content,
+            |x| x.start_heading(*level),
+            |x| x.end_heading(*level),
+        ),
+        BlockKind::BlockQuote(lines) => {
+            renderer.source(span.start..span.start);
+            renderer.start_block_quote();
+            let mut pos = span.start;
+            for line in lines {
+                let end = line.last().map(|x| x.span.end).unwrap_or(pos).max(pos);
+                container(
+                    renderer,
+                    &(pos..end),
+                    line,
+                    R::start_block_quote_line,
+                    R::end_block_quote_line,
+                );
+                pos = end;
+            }
+            renderer.source(pos..span.end);
+            renderer.end_block_quote();
+        }
+        BlockKind::CodeBlock { language, code } => {
+            renderer.source(span.clone());
+            renderer.code_block(language.as_deref(), code);
+        }
+        BlockKind::List { start, items } => {
+            renderer.source(span.start..span.start);
+            renderer.start_list(*start);
+            for item in items {
+                let (open, _) = markers(&item.span, &item.content);
+                renderer.source(open);
+                renderer.start_list_item();
+                render_inlines(&item.content, renderer);
+                for child in &item.children {
+                    render_block(child, renderer);
+                }
+                renderer.source(item.span.end..item.span.end);
+                renderer.end_list_item();
+            }
+            renderer.source(span.end..span.end);
+            renderer.end_list(start.is_some());
+        }
+    }
+}
+
+fn render_inlines<R: Renderer + ?Sized>(inlines: &[Inline], renderer: &mut R) {
+    for inline in inlines {
+        let span = &inline.span;
+        // containers replace this with the span of their markers
+        renderer.source(span.clone());
+        match &inline.kind {
+            InlineKind::Text(text) => renderer.text(text),
+            InlineKind::LineBreak => renderer.line_break(),
+            InlineKind::Emphasis(content) => {
+                container(renderer, span, content, R::start_emphasis, R::end_emphasis)
+            }
+            InlineKind::Strong(content) => {
+                container(renderer, span, content, R::start_strong, R::end_strong)
+            }
+            InlineKind::Underline(content) => container(
+                renderer,
+                span,
+                content,
+                R::start_underline,
+                R::end_underline,
+            ),
+            InlineKind::Strikethrough(content) => container(
+                renderer,
+                span,
+                content,
+                R::start_strikethrough,
+                R::end_strikethrough,
+            ),
+            InlineKind::Spoiler(content) => {
+                container(renderer, span, content, R::start_spoiler, R::end_spoiler)
+            }
+            InlineKind::Code(code) => renderer.code(code),
+            InlineKind::Link { url, content } => {
+                container(renderer, span, content, |x| x.start_link(url), R::end_link)
+            }
+            InlineKind::Mention(name) => renderer.mention(name),
+            InlineKind::Emoji(name) => renderer.emoji(name),
+        }
+    }
+}
+
+// renders the content of a node between its start and end callbacks
+fn container<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    span: &Range<usize>,
+    content: &[Inline],
+    start: impl FnOnce(&mut R),
+    end: impl FnOnce(&mut R),
+) {
+    let (open, close) = markers(span, content);
+    renderer.source(open);
+    start(renderer);
+    render_inlines(content, renderer);
+    renderer.source(close);
+    end(renderer);
+}
+
+// the markdown before and after the content of a node, like the `**` on either side of bold text
+fn markers(span: &Range<usize>, content: &[Inline]) -> (Range<usize>, Range<usize>) {
+    let start = content
+        .first()
+        .map(|x| x.span.start)
+        .unwrap_or(span.end)
+        .clamp(span.start, span.end);
+    let end = content
+        .last()
+        .map(|x| x.span.end)
+        .unwrap_or(span.end)
+        .clamp(start, span.end);
+    (span.start..start, end..span.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    // renders the text for a terminal
+    #[derive(Default)]
+    struct AnsiRenderer {
+        output: String,
+    }
+
+    impl Renderer for AnsiRenderer {
+        fn text(&mut self, text: &str) {
+            self.output += text;
+        }
+
+        fn line_break(&mut self) {
+            self.output.push('\n');
+        }
+
+        fn code(&mut self, code: &str) {
+            self.output += code;
+        }
+
+        fn code_block(&mut self, _language: Option<&str>, code: &str) {
+            self.output += code;
+        }
+
+        fn start_strong(&mut self) {
+            self.output += "\x1b[1m";
+        }
+
+        fn end_strong(&mut self) {
+            self.output += "\x1b[22m";
+        }
+    }
+
+    #[test]
+    fn test_custom_renderer() {
+        let mut renderer = AnsiRenderer::default();
+        parse("**hello** *world*\n> `quote`").render(&mut renderer);
+        assert_eq!(renderer.output, "\x1b[1mhello\x1b[22m world\nquote");
+    }
+
+    #[test]
+    fn test_heading_callbacks() {
+        #[derive(Default)]
+        struct Levels(Vec<u8>);
+        impl Renderer for Levels {
+            fn text(&mut self, _text: &str) {}
+            fn line_break(&mut self) {}
+            fn code(&mut self, _code: &str) {}
+            fn code_block(&mut self, _language: Option<&str>, _code: &str) {}
+            fn start_heading(&mut self, level: u8) {
+                self.0.push(level);
+            }
+        }
+
+        let mut renderer = Levels::default();
+        parse("# a\n### b\n##### c").render(&mut renderer);
+        assert_eq!(renderer.0, vec![1, 3, 5]);
+    }
+}
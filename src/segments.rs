@@ -0,0 +1,84 @@
+// what each part of the html is, so the caller can change the text without touching anything else
+use std::ops::Range;
+
+/// a piece of the html returned by `text_to_html_segments`, and the markdown it came from. together, the segments
+/// cover all of the html, in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    /// byte range of the markdown. for markup, this is the marker it replaced, which may be empty.
+    pub input: Range<usize>,
+    /// byte range of the html
+    pub output: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// escaped text, including line breaks and mentions which weren't resolved. this is the only kind which is
+    /// safe to change.
+    Text,
+    /// `<code>` or a code block, including the tags
+    Code,
+    /// `<a>`, including the tags and the link text
+    Link,
+    /// a resolved mention's `<span>`, including the tags
+    Mention,
+    /// a custom emoji's `<img>`
+    Emoji,
+    /// tags like `<strong>`, and the newlines between lines of a block quote or list
+    Markup,
+}
+
+// the old ranges - everything that isn't code, a link, a mention or an image
+pub(crate) fn text_ranges(segments: &[Segment]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for segment in segments {
+        if !matches!(segment.kind, SegmentKind::Text | SegmentKind::Markup) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end == segment.output.start => range.end = segment.output.end,
+            _ => ranges.push(segment.output.clone()),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{text_to_html, text_to_html_segments};
+
+    #[test]
+    fn test_segments() {
+        let test_str = "**a** `b` [c](https://c.com)\n- d &";
+        let (html, segments) = text_to_html_segments(test_str);
+        let found: Vec<(SegmentKind, &str, &str)> = segments
+            .iter()
+            .map(|x| (x.kind, &test_str[x.input.clone()], &html[x.output.clone()]))
+            .collect();
+        let expected = vec![
+            (SegmentKind::Markup, "**", "<strong>"),
+            (SegmentKind::Text, "a", "a"),
+            (SegmentKind::Markup, "**", "</strong>"),
+            (SegmentKind::Text, " ", " "),
+            (SegmentKind::Code, "`b`", "<code>b</code>"),
+            (SegmentKind::Text, " ", " "),
+            (
+                SegmentKind::Link,
+                "[c](https://c.com)",
+                "<a href=\"https://c.com\">c</a>",
+            ),
+            (SegmentKind::Text, "\n", "\n"),
+            (SegmentKind::Markup, "- ", "<ul>\n<li>"),
+            (SegmentKind::Text, "d &", "d &amp;"),
+            (SegmentKind::Markup, "", "</li>\n</ul>"),
+        ];
+        assert_eq!(found, expected);
+
+        // the segments cover all of the html
+        let covered: usize = segments.iter().map(|x| x.output.len()).sum();
+        assert_eq!(covered, html.len());
+        assert_eq!(text_ranges(&segments), text_to_html(test_str).1);
+    }
+}
@@ -0,0 +1,112 @@
+// changes the user's text while it is rendered, so the caller doesn't have to fix up the ranges afterwards
+use std::fmt;
+
+use crate::html::write_escaped;
+
+/// called by `HtmlRenderer` with each run of text the user wrote, including link text. code, urls, mentions and
+/// tags are never passed to it. see `HtmlRenderer::with_transformer`.
+///
+/// a closure which returns the new text can be used as a transformer. the text it returns is escaped.
+pub trait TextTransformer {
+    /// writes the html for `text`. a run ends at a line break or any markup, so `a *b*` is passed as `a ` and then
+    /// `b`.
+    fn transform(&mut self, text: &str, output: &mut HtmlOutput);
+}
+
+impl<F: FnMut(&str) -> String> TextTransformer for F {
+    fn transform(&mut self, text: &str, output: &mut HtmlOutput) {
+        output.text(&self(text));
+    }
+}
+
+/// where a `TextTransformer` writes its html
+pub struct HtmlOutput<'a> {
+    // errors are kept by the renderer
+    html: &'a mut dyn fmt::Write,
+}
+
+impl<'a> HtmlOutput<'a> {
+    pub(crate) fn new(html: &'a mut dyn fmt::Write) -> Self {
+        Self { html }
+    }
+
+    /// writes `text`, escaped so it is only ever text
+    pub fn text(&mut self, text: &str) {
+        let _ = write_escaped(text, self.html);
+    }
+
+    /// writes `html` as is, for tags like `<mark>`. anything the user wrote has to go through `text` instead.
+    pub fn markup(&mut self, html: &str) {
+        let _ = self.html.write_str(html);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, HtmlRenderer, Mention};
+
+    fn render(text: &str, transformer: impl TextTransformer) -> String {
+        let resolver = |name: &str| Mention::User {
+            display_name: name.to_string(),
+            id: name.to_string(),
+        };
+        let mut renderer = HtmlRenderer::new()
+            .with_mentions(resolver)
+            .with_transformer(transformer);
+        parse(text).render(&mut renderer);
+        renderer.finish().0
+    }
+
+    #[test]
+    fn test_transformer() {
+        let filter = |text: &str| text.replace("darn", "****");
+        let test_str =
+            "darn it, `darn` **darn** [darn](https://darn.com) https://darn.com @darn\ndarn <3";
+        let expected = "**** it, <code>darn</code> <strong>****</strong> <a href=\"https://darn.com\">****</a> <a href=\"https://darn.com\">https://darn.com</a> <span class=\"mention\" data-id=\"darn\" data-name=\"darn\">@darn</span>\n**** &lt;3";
+        assert_eq!(render(test_str, filter), expected);
+    }
+
+    #[test]
+    fn test_escaped() {
+        // the text a closure returns can't add tags
+        let upper = |text: &str| text.to_uppercase();
+        assert_eq!(render("a <b>", upper), "A &lt;B&gt;");
+    }
+
+    // wraps `b&c` in `<mark>`
+    struct Highlight;
+
+    impl TextTransformer for Highlight {
+        fn transform(&mut self, text: &str, output: &mut HtmlOutput) {
+            for (idx, word) in text.split(' ').enumerate() {
+                if idx > 0 {
+                    output.text(" ");
+                }
+                match word {
+                    "b&c" => {
+                        output.markup("<mark>");
+                        output.text(word);
+                        output.markup("</mark>");
+                    }
+                    _ => output.text(word),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_highlight() {
+        let (html, segments) = {
+            let mut renderer = HtmlRenderer::new().with_transformer(Highlight);
+            parse("a b&c _b&c_ `b&c`").render(&mut renderer);
+            renderer.finish_segments()
+        };
+        assert_eq!(
+            html,
+            "a <mark>b&amp;c</mark> <em><mark>b&amp;c</mark></em> <code>b&amp;c</code>"
+        );
+        // the segments are for the transformed html
+        assert_eq!(segments.last().map(|x| x.output.end), Some(html.len()));
+    }
+}
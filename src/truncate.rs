@@ -0,0 +1,260 @@
+// cuts a Document down to a number of visible characters, for previews. the tree is truncated rather than the
+// output, so every renderer closes its tags the same way it always does.
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::ast::{Block, BlockKind, Document, Inline, InlineKind, ListItem};
+
+impl Document {
+    /// keeps the first `max` visible characters, and adds `ellipsis` where the text was cut. markers aren't
+    /// counted, characters are grapheme clusters, and line breaks count as one character. a mention counts as
+    /// `@name`, and an emoji counts as one character. spans still point at the original text, and the ellipsis has
+    /// an empty span where the cut was made.
+    pub fn truncate(&self, max: usize, ellipsis: &str) -> Document {
+        let mut truncator = Truncator {
+            remaining: max,
+            ellipsis,
+            done: false,
+        };
+        Document {
+            blocks: truncator.blocks(&self.blocks),
+        }
+    }
+}
+
+struct Truncator<'a> {
+    // how many more characters can be kept
+    remaining: usize,
+    ellipsis: &'a str,
+    // true once the text has been cut
+    done: bool,
+}
+
+impl Truncator<'_> {
+    fn blocks(&mut self, blocks: &[Block]) -> Vec<Block> {
+        let mut output = Vec::new();
+        for block in blocks {
+            if self.done {
+                break;
+            }
+            if self.remaining == 0 {
+                let ellipsis = self.ellipsis(block.span.start);
+                output.push(Block::new(
+                    BlockKind::Paragraph(vec![ellipsis]),
+                    block.span.start..block.span.start,
+                ));
+                break;
+            }
+
+            let kind = match &block.kind {
+                BlockKind::Paragraph(content) => BlockKind::Paragraph(self.inlines(content)),
+                BlockKind::Heading { level, content } => BlockKind::Heading {
+                    level: *level,
+                    content: self.inlines(content),
+                },
+                BlockKind::BlockQuote(lines) => {
+                    let mut output: Vec<Vec<Inline>> = Vec::new();
+                    for line in lines {
+                        if let Some(last) = output.last_mut() {
+                            if !self.separator(last) {
+                                break;
+                            }
+                        }
+                        output.push(self.inlines(line));
+                    }
+                    BlockKind::BlockQuote(output)
+                }
+                BlockKind::CodeBlock { language, code } => {
+                    let (code, cut) = self.cut(code);
+                    let kind = BlockKind::CodeBlock {
+                        language: language.clone(),
+                        code: code.to_string(),
+                    };
+                    output.push(Block::new(kind, block.span.clone()));
+                    // the ellipsis goes after the code block, not inside of it
+                    if cut {
+                        let ellipsis = self.ellipsis(block.span.end);
+                        output.push(Block::new(
+                            BlockKind::Paragraph(vec![ellipsis]),
+                            block.span.end..block.span.end,
+                        ));
+                    }
+                    continue;
+                }
+                BlockKind::List { start, items } => {
+                    let mut output: Vec<ListItem> = Vec::new();
+                    for item in items {
+                        if let Some(last) = output.last_mut() {
+                            if !self.separator(&mut last.content) {
+                                break;
+                            }
+                        }
+                        let mut content = self.inlines(&item.content);
+                        // nested lists start on a new line
+                        let children =
+                            match item.children.is_empty() || !self.separator(&mut content) {
+                                true => Vec::new(),
+                                false => self.blocks(&item.children),
+                            };
+                        output.push(ListItem {
+                            content,
+                            children,
+                            span: item.span.clone(),
+                        });
+                        if self.done {
+                            break;
+                        }
+                    }
+                    BlockKind::List {
+                        start: *start,
+                        items: output,
+                    }
+                }
+            };
+            output.push(Block::new(kind, block.span.clone()));
+        }
+        output
+    }
+
+    // counts the newline between block quote lines or list items. if there is no room for it, the ellipsis is added
+    // to the end of the previous line and false is returned.
+    fn separator(&mut self, previous: &mut Vec<Inline>) -> bool {
+        if self.done {
+            return false;
+        }
+        if self.remaining == 0 {
+            let pos = previous.last().map(|x| x.span.end).unwrap_or_default();
+            previous.push(self.ellipsis(pos));
+            return false;
+        }
+        self.remaining -= 1;
+        true
+    }
+
+    fn inlines(&mut self, inlines: &[Inline]) -> Vec<Inline> {
+        let mut output = Vec::new();
+        for inline in inlines {
+            if self.done {
+                break;
+            }
+            if self.remaining == 0 {
+                output.push(self.ellipsis(inline.span.start));
+                break;
+            }
+
+            let span = inline.span.clone();
+            let kind = match &inline.kind {
+                InlineKind::Text(text) => {
+                    let (kept, cut) = self.cut(text);
+                    if cut {
+                        // the ellipsis replaces the space at the end of a word
+                        let kept = kept.trim_end();
+                        let text = format!("{kept}{}", self.ellipsis);
+                        output.push(Inline::new(InlineKind::Text(text), span));
+                        break;
+                    }
+                    inline.kind.clone()
+                }
+                InlineKind::Code(code) => {
+                    let (kept, cut) = self.cut(code);
+                    if cut {
+                        output.push(Inline::new(
+                            InlineKind::Code(kept.to_string()),
+                            span.clone(),
+                        ));
+                        output.push(self.ellipsis(span.end));
+                        break;
+                    }
+                    inline.kind.clone()
+                }
+                InlineKind::LineBreak | InlineKind::Emoji(_) => {
+                    self.remaining -= 1;
+                    inline.kind.clone()
+                }
+                InlineKind::Mention(name) => {
+                    let len = name.graphemes(true).count() + 1;
+                    if len > self.remaining {
+                        output.push(self.ellipsis(span.start));
+                        break;
+                    }
+                    self.remaining -= len;
+                    inline.kind.clone()
+                }
+                InlineKind::Emphasis(content) => InlineKind::Emphasis(self.inlines(content)),
+                InlineKind::Strong(content) => InlineKind::Strong(self.inlines(content)),
+                InlineKind::Underline(content) => InlineKind::Underline(self.inlines(content)),
+                InlineKind::Strikethrough(content) => {
+                    InlineKind::Strikethrough(self.inlines(content))
+                }
+                InlineKind::Spoiler(content) => InlineKind::Spoiler(self.inlines(content)),
+                InlineKind::Link { url, content } => InlineKind::Link {
+                    url: url.clone(),
+                    content: self.inlines(content),
+                },
+            };
+            output.push(Inline::new(kind, span));
+        }
+        output
+    }
+
+    // returns the start of the text which fits, and whether any of it was cut
+    fn cut<'b>(&mut self, text: &'b str) -> (&'b str, bool) {
+        match text.grapheme_indices(true).nth(self.remaining) {
+            Some((end, _)) => {
+                self.remaining = 0;
+                self.done = true;
+                (&text[..end], true)
+            }
+            None => {
+                self.remaining -= text.graphemes(true).count();
+                (text, false)
+            }
+        }
+    }
+
+    fn ellipsis(&mut self, pos: usize) -> Inline {
+        self.done = true;
+        Inline::new(InlineKind::Text(self.ellipsis.to_string()), pos..pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, text_to_html_truncated};
+
+    #[test]
+    fn test_truncate() {
+        let test_str = "**hello *wonderful*** world";
+        let (html, _) = text_to_html_truncated(test_str, 10, "…");
+        assert_eq!(html, "<strong>hello <em>wond…</em></strong>");
+        let (html, _) = text_to_html_truncated(test_str, 100, "…");
+        assert_eq!(html, text_to_html_truncated(test_str, 21, "…").0);
+        let (html, _) = text_to_html_truncated(test_str, 15, "...");
+        assert_eq!(html, "<strong>hello <em>wonderful</em></strong>...");
+    }
+
+    #[test]
+    fn test_truncate_graphemes() {
+        let test_str = "👩‍👩‍👧e\u{301}漢字 ~~`abcd`~~";
+        let (html, _) = text_to_html_truncated(test_str, 3, "…");
+        assert_eq!(html, "👩‍👩‍👧e\u{301}漢…");
+        let (html, _) = text_to_html_truncated(test_str, 7, "…");
+        assert_eq!(html, "👩‍👩‍👧e\u{301}漢字 <s><code>ab</code>…</s>");
+    }
+
+    #[test]
+    fn test_truncate_blocks() {
+        let (html, _) = text_to_html_truncated("> a\n> b\n- c\n- d", 2, "…");
+        assert_eq!(html, "<blockquote>\n<p>a</p>\n<p>…</p>\n</blockquote>");
+        let (html, _) = text_to_html_truncated("> a\n> b", 1, "…");
+        assert_eq!(html, "<blockquote>\n<p>a…</p>\n</blockquote>");
+        let (html, _) = text_to_html_truncated("# a\n```rust\nlet a = 0;```b", 6, "…");
+        assert_eq!(
+            html,
+            "<h1>a</h1>\n<pre><code class=\"language-rust\">let </code></pre>…"
+        );
+        let document = parse("- a\n- b\n  - c").truncate(5, "…");
+        assert_eq!(document.to_plain(), "- a\n- b\n  - c");
+        let document = parse("- a\n- b\n  - c").truncate(4, "…");
+        assert_eq!(document.to_plain(), "- a\n- b…");
+    }
+}